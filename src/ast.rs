@@ -2,12 +2,14 @@
 
 use std::fmt;
 
-use crate::token::{ Token };
+use crate::token::{ Span, Token };
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone)]
 pub struct AST {
   pub token: Token,
   pub children: Vec<AST>,
+  // where in the source the node is, if known, which is not part of the tree when compared or shown
+  pub span: Option<Span>,
 }
 
 impl AST {
@@ -15,13 +17,34 @@ impl AST {
   pub fn new(token: Token, children: Vec<AST>) -> AST {
     AST {
       token: token,
-      children: children
+      children: children,
+      span: None
+    }
+  }
+  // with_span(Token, Vec<AST>, Span) -> AST
+  pub fn with_span(token: Token, children: Vec<AST>, span: Span) -> AST {
+    AST {
+      token: token,
+      children: children,
+      span: Some(span)
     }
   }
 }
 
+impl PartialEq for AST {
+  fn eq(&self, other: &AST) -> bool {
+    return self.token == other.token && self.children == other.children;
+  }
+}
+
+impl std::fmt::Debug for AST {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("AST").field("token", &self.token).field("children", &self.children).finish()
+  }
+}
+
 impl std::fmt::Display for AST {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
     }
-}
\ No newline at end of file
+}
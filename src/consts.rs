@@ -6,11 +6,34 @@ pub const PANIC_SYNTAX              : &str = "Invalid syntax";
 pub const PANIC_TYPE_DECLARATION    : &str = "Invalid type declaration";
 pub const PANIC_ARRAY               : &str = "Invalid array type";
 pub const PANIC_VAR_NOT_DECLARAED   : &str = "Variable not declared";
+pub const PANIC_ROUTINE_NOT_DECLARED: &str = "Procedure or function not declared";
+pub const PANIC_ARGUMENTS           : &str = "Wrong number of arguments";
+pub const PANIC_VAR_ARGUMENT        : &str = "Variable expected";
+pub const PANIC_TYPE_MISMATCH       : &str = "Type mismatch";
+pub const PANIC_CONSTANT            : &str = "Not a constant expression";
+pub const PANIC_CONSTANT_ASSIGNMENT : &str = "Cannot assign to a constant";
+pub const PANIC_DIVISION_BY_ZERO    : &str = "Division by zero";
+pub const PANIC_OVERFLOW            : &str = "Integer overflow";
+pub const PANIC_NONLOCAL            : &str = "Non-local access not supported";
+pub const PANIC_DUPLICATE           : &str = "Duplicate identifier";
+pub const PANIC_MEMORY              : &str = "Not enough memory";
 pub const PANIC_COMPILE             : &str = "Could not compile";
 pub const PANIC_WRITE               : &str = "Could not write to file";
+pub const PANIC_OUTPUT              : &str = "Inputs have the same output";
 pub const PANIC_READ                : &str = "Could not read from file";
 pub const PANIC_FILE                : &str = "No source file provided";
 pub const PANIC_EVAL                : &str = "Could not evaluate";
+pub const PANIC_ASSEMBLE            : &str = "Could not assemble";
+pub const PANIC_COMMENT             : &str = "Unterminated comment";
+pub const PANIC_STRING              : &str = "Unterminated string";
+pub const PANIC_CHAR_CODE           : &str = "Invalid character code";
+pub const PANIC_INTEGER_RANGE       : &str = "Integer literal out of range";
+pub const PANIC_STEPS               : &str = "Too many steps";
+pub const PANIC_DEPTH               : &str = "Too many nested calls";
+pub const PANIC_PROJECT             : &str = "Invalid project file";
+pub const PANIC_DIRECTIVE           : &str = "Invalid directive";
+pub const PANIC_INCLUDE             : &str = "Include file not found";
+pub const PANIC_ENDIF               : &str = "Missing $ENDIF";
 // formatting
 pub const FORMAT_SPACE              : &str = " ";
 pub const FORMAT_TAB                : &str = "  ";
@@ -32,10 +55,27 @@ pub const WASM_LESS_THAN            : &str = ".lt";
 pub const WASM_LESS_EQUAL           : &str = ".le";
 pub const WASM_NEGATION             : &str = ".neg";
 pub const WASM_CONSTANT             : &str = ".const";
-pub const WASM_VARIABLE             : &str = "get_local";
-pub const WASM_ASSIGNMENT           : &str = "set_local";
+pub const WASM_VARIABLE             : &str = "local.get";
+pub const WASM_ASSIGNMENT           : &str = "local.set";
 pub const WASM_DECLARATION          : &str = "param";
+pub const WASM_LOCAL                : &str = "local";
+pub const WASM_GLOBAL               : &str = "global";
+pub const WASM_MUTABLE              : &str = "mut";
+pub const WASM_GLOBAL_VARIABLE      : &str = "global.get";
+pub const WASM_GLOBAL_ASSIGNMENT    : &str = "global.set";
+pub const WASM_EQUAL_ZERO           : &str = ".eqz";
+pub const WASM_LOAD                 : &str = ".load";
+pub const WASM_STORE                : &str = ".store";
+pub const WASM_MEMORY               : &str = "memory";
+pub const WASM_FILL                 : &str = "memory.fill";
+pub const WASM_SIGNED               : &str = "_s";
+pub const WASM_UNSIGNED             : &str = "_u";
 pub const WASM_RESULT               : &str = "result";
+pub const WASM_CALL                 : &str = "call";
+pub const WASM_DROP                 : &str = "drop";
+pub const WASM_SELECT               : &str = "select";
+pub const WASM_UNREACHABLE          : &str = "unreachable";
+pub const WASM_IMPORT               : &str = "import";
 pub const WASM_EXPORT               : &str = "export";
 pub const WASM_FUNCTION             : &str = "func";
 pub const WASM_MODULE               : &str = "module";
@@ -49,4 +89,6 @@ pub const WASM_THEN                 : &str = "then";
 pub const WASM_ELSE                 : &str = "else";
 // misc
 pub const WASM_WAT                  : &str = ".wat";
-pub const WASM_JS                   : &str = ".js";
\ No newline at end of file
+pub const WASM_JS                   : &str = ".js";
+pub const WASM_BINARY               : &str = ".wasm";
+pub const PROJECT_FILE              : &str = "pas2wat.toml";
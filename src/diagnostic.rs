@@ -0,0 +1,73 @@
+use std::fmt;
+
+use crate::token::Span;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+  pub message: String,
+  // where in the source the diagnostic is, if known
+  pub span: Option<Span>,
+  // included file the span is in, none for the source itself
+  pub file: Option<String>,
+}
+
+impl Diagnostic {
+  // new(String) -> Diagnostic
+  pub fn new(message: String) -> Diagnostic {
+    Diagnostic {
+      message: message,
+      span: None,
+      file: None,
+    }
+  }
+  // with_span(String, Span) -> Diagnostic
+  pub fn with_span(message: String, span: Span) -> Diagnostic {
+    Diagnostic {
+      message: message,
+      span: Some(span),
+      file: None,
+    }
+  }
+}
+
+impl std::fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match (&self.file, self.span) {
+      (Some(_file), Some(_span)) => write!(f, "{}:{} : {}", _file, _span, self.message),
+      (Some(_file), None) => write!(f, "{} : {}", _file, self.message),
+      (None, Some(_span)) => write!(f, "{} : {}", _span, self.message),
+      (None, None) => write!(f, "{}", self.message)
+    }
+  }
+}
+
+impl std::error::Error for Diagnostic {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostics {
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+  // new(Vec<Diagnostic>) -> Diagnostics
+  pub fn new(diagnostics: Vec<Diagnostic>) -> Diagnostics {
+    Diagnostics {
+      diagnostics: diagnostics,
+    }
+  }
+}
+
+impl From<Diagnostic> for Diagnostics {
+  fn from(diagnostic: Diagnostic) -> Diagnostics {
+    Diagnostics::new(vec![diagnostic])
+  }
+}
+
+impl std::fmt::Display for Diagnostics {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let lines: Vec<String> = self.diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+    write!(f, "{}", lines.join("\n"))
+  }
+}
+
+impl std::error::Error for Diagnostics {}
@@ -0,0 +1,68 @@
+use crate::consts::*;
+use crate::token::{ Type, Token, Span };
+use crate::ast::AST;
+
+// tokens(&[(Span, Token)]) -> String
+pub fn tokens(tokens: &[(Span, Token)]) -> String {
+  let mut text = String::new();
+  for (_span, _token) in tokens {
+    text.push_str(&format!("{:>8}{}{:?}{}", _span.to_string(), FORMAT_TAB, _token, FORMAT_NEWLINE));
+  }
+  return text;
+}
+
+// tree(&AST) -> String
+pub fn tree(node: &AST) -> String {
+  let mut text = String::new();
+  visit(node, 0, &mut text);
+  return text;
+}
+
+// visit(&AST, usize, &mut String)
+fn visit(node: &AST, depth: usize, text: &mut String) {
+  text.push_str(&format!("{}{:?}{}", FORMAT_TAB.repeat(depth), node.token, FORMAT_NEWLINE));
+  for _child in &node.children {
+    visit(_child, depth + 1, text);
+  }
+}
+
+// symbols(&[(Token, Type)]) -> String
+pub fn symbols(symbols: &[(Token, Type)]) -> String {
+  let mut rows = vec![("name".to_string(), "type".to_string())];
+  for (_token, _type) in symbols {
+    match _token {
+      Token::ID(_string) => rows.push((_string.clone(), format!("{:?}", _type))),
+      _ => rows.push((format!("{:?}", _token), format!("{:?}", _type))),
+    }
+  }
+  let width = rows.iter().map(|row| row.0.chars().count()).max().unwrap_or(0);
+  let mut text = String::new();
+  for (_name, _type) in rows {
+    text.push_str(&format!("{:<width$}{}{}{}", _name, FORMAT_TAB, _type, FORMAT_NEWLINE, width = width));
+  }
+  return text;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::options::{ Dialect, Options };
+
+  #[test]
+  fn tokens() {
+    assert_eq!(
+      super::tokens(&[(Span::new(0, 1, 1, 1), Token::ID("x".to_string())), (Span::new(2, 4, 1, 3), Token::ASSIGN)]),
+      "     1:1  ID(\"x\")\n     1:3  ASSIGN\n"
+    );
+    // reserved words in the dump follow the dialect
+    let options = Options { dialect: Dialect::DELPHI, ..Options::default() };
+    assert_eq!(super::tokens(&crate::tokenize_with_spans("class", &options).unwrap()), "     1:1  CLASS\n");
+    assert_eq!(super::tokens(&crate::tokenize_with_spans("class", &Options::default()).unwrap()), "     1:1  ID(\"class\")\n");
+  }
+
+  #[test]
+  fn tree() {
+    let node = AST::new(Token::PLUS, vec![AST::new(Token::INTEGER(1), vec![]), AST::new(Token::INTEGER(2), vec![])]);
+    assert_eq!(super::tree(&node), "PLUS\n  INTEGER(1)\n  INTEGER(2)\n");
+  }
+}
@@ -1,247 +1,604 @@
-use std::fs::File;
-use std::io::{ BufWriter, Write };
+use std::convert::TryFrom;
+use std::io::Write;
 
-use crate::config::*;
 use crate::consts::*;
-use crate::token::{ Token };
+use crate::token::{ Type, Token };
 use crate::ast::AST;
+use crate::names;
+use crate::diagnostic::Diagnostic;
 use crate::parser::Parser;
+use crate::options::{ Options, Target };
 
-pub struct Emitter {
-  parser: Parser,
-  file: BufWriter<File>,
+// export name of the entry point on WASI
+const WASI_START                    : &str = "_start";
+// built-in procedure that writes numbers, imported from console.log which the glue passes in
+const WRITELN                       : &str = "writeln";
+const IMPORT_MODULE                 : &str = "console";
+const IMPORT_WRITE                  : &str = "log";
+// on WASI writeln is a function of the module, writing the text of the number to stdout with fd_write
+const WASI_MODULE                   : &str = "wasi_snapshot_preview1";
+const WASI_WRITE                    : &str = "fd_write";
+const WASI_STDOUT                   : i64 = 1;
+// bytes on the shadow stack for the iovec, the count written and the text, which is at most 28 bytes
+const WASI_BUFFER                   : i64 = 64;
+// local holding the result of a function, assigned by the name of the function or Result
+const RESULT                        : &str = "result";
+// the shadow stack grows down from the end of memory, globals in memory are at the start
+const PAGE                          : i64 = 65536;
+// bytes left for the shadow stack at least, and the most pages, so the end of memory is still an i32 address
+const STACK_SIZE                    : i64 = 32768;
+const PAGES                         : i64 = 65535;
+const STACK_POINTER                 : &str = "sp";
+const FRAME_POINTER                 : &str = "frame";
+// local holding an index known only when running
+const INDEX                         : &str = "index";
+// bytes for each variable in memory, enough for a real
+const SLOT                          : i64 = 8;
+
+// where a variable is kept
+#[derive(Clone, Debug, PartialEq)]
+enum Storage {
+  GLOBAL,
+  LOCAL,
+  // in memory, at an address for globals and at an offset in the frame of the function for locals
+  STATIC(i64),
+  FRAME(i64),
+  // parameters passed by reference hold the address, CONSTANT ones cannot be assigned to
+  REFERENCE,
+  CONSTANT,
+  // arrays are in memory, with where the first element is kept and the bounds
+  ARRAY(Box<Storage>, i64, i64),
+}
+
+// types of the parameters of a routine, and how each is passed
+type Parameters = Vec<(Type, Storage)>;
+
+pub struct Emitter<W: Write> {
+  writer: W,
+  verbose: bool,
   tab_pos: i32,
-  require_i32: bool,
+  // names of the functions being emitted, outermost first, used to mangle names
+  scopes: Vec<String>,
+  target: Target,
+  // a program is being emitted, so every variable must be declared
+  program: bool,
+  // declared variables by canonical name
+  globals: Vec<(String, Type, Storage)>,
+  locals: Vec<(String, Type, Storage)>,
+  // declared procedures and functions by canonical name, with the mangled name, parameter types and result type
+  routines: Vec<(String, String, Parameters, Option<Type>)>,
+  // canonical name of the function being emitted, which is its result when assigned
+  function: Option<String>,
+  // keys of the parameters and variables of the routines enclosing the one being emitted
+  enclosing: Vec<Vec<String>>,
+  // variables passed to var parameters by canonical name, and whether any parameter is passed by reference
+  addressed: Vec<String>,
+  memory: bool,
+  // next address for globals in memory, and size of the frame of the function being emitted
+  statics: i64,
+  frame: i64,
+  // lowest address the shadow stack may reach, the end of the globals in memory
+  bottom: i64,
 }
 
-impl Emitter {
+impl<W: Write> Emitter<W> {
   // new : Emitter
-  pub fn new(parser: Parser, file: BufWriter<File>) -> Emitter {
+  pub fn new(writer: W, options: &Options) -> Emitter<W> {
     let emitter = Emitter {
-      parser: parser,
-      file: file,
+      writer: writer,
+      // include comments in generated code
+      verbose: options.comments,
       tab_pos: 0,
-      // control flow require condition to be i32
-      require_i32: false,
+      scopes: vec![],
+      target: options.target,
+      program: false,
+      globals: vec![],
+      locals: vec![],
+      routines: vec![],
+      function: None,
+      enclosing: vec![],
+      addressed: vec![],
+      memory: false,
+      statics: 0,
+      bottom: 0,
+      frame: 0,
     };
     return emitter;
   }
+  // ntype(&Type) -> &str
+  fn ntype(_type: &Type) -> &'static str {
+    // booleans are i32, 1 for true and 0 for false
+    match _type {
+      Type::INTEGER | Type::BOOLEAN => return NTYPE_INTEGER,
+      Type::REAL => return NTYPE_REAL,
+    }
+  }
+  // key(&AST) -> String
+  fn key(node: &AST) -> String {
+    // variables by canonical name, array elements with their index
+    match (&node.token, node.children.first().map(|child| &child.token)) {
+      (Token::ID(_string), Some(Token::INTEGER(_int))) => return format!("{}[{}]", names::canonical(_string), _int),
+      (Token::ID(_string), _) => return names::canonical(_string),
+      _ => return format!("{:?}", node.token)
+    }
+  }
+  // indexed(&AST) -> bool
+  fn indexed(node: &AST) -> bool {
+    // an array element with an index known only when running
+    match (&node.token, node.children.first().map(|child| &child.token)) {
+      (Token::ID(_), Some(Token::INTEGER(_))) | (Token::ID(_), Some(Token::RANGE)) | (Token::ID(_), None) => return false,
+      (Token::ID(_), Some(_)) => return true,
+      _ => return false
+    }
+  }
+  // bounds(&AST) -> Result<(i64, i64), Diagnostic>
+  fn bounds(node: &AST) -> Result<(i64, i64), Diagnostic> {
+    // the range of an array has the first and last index as children
+    match (node.children.first().map(|child| &child.token), node.children.last().map(|child| &child.token)) {
+      (Some(Token::INTEGER(_low)), Some(Token::INTEGER(_high))) => return Ok((*_low, *_high)),
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
+    }
+  }
+  // offset(&Storage, i64) -> Storage
+  fn offset(storage: &Storage, bytes: i64) -> Storage {
+    // a place in memory that many bytes further on
+    match storage {
+      Storage::STATIC(_address) => return Storage::STATIC(_address + bytes),
+      Storage::FRAME(_offset) => return Storage::FRAME(_offset + bytes),
+      _ => return storage.clone()
+    }
+  }
+  // lookup(&AST) -> Result<(Type, Storage), Diagnostic>
+  fn lookup(&self, node: &AST) -> Result<(Type, Storage), Diagnostic> {
+    // the type of a variable or of an element with a constant index, and where it is kept
+    let (_type, storage) = self.entry(node)?;
+    match (storage, node.children.first().map(|child| &child.token)) {
+      (Storage::ARRAY(_first, _low, _high), Some(Token::INTEGER(_int))) if _low <= *_int && *_int <= _high => return Ok((_type, Emitter::<W>::offset(&_first, (*_int - _low) * SLOT))),
+      (Storage::ARRAY(..), None) => return Err(Emitter::<W>::error(node, format!("{} : {:?}", PANIC_TYPE_MISMATCH, node.token))),
+      (_, Some(_)) => return Err(Emitter::<W>::error(node, format!("{} : {}", PANIC_VAR_NOT_DECLARAED, Emitter::<W>::key(node)))),
+      (_storage, None) => return Ok((_type, _storage))
+    }
+  }
+  // entry(&AST) -> Result<(Type, Storage), Diagnostic>
+  fn entry(&self, node: &AST) -> Result<(Type, Storage), Diagnostic> {
+    // the type of a variable or array, and where it is kept, from the innermost scope that declares the name
+    let name = match &node.token {
+      Token::ID(_string) => names::canonical(_string),
+      _ => Emitter::<W>::key(node)
+    };
+    if let Some((_, _type, _storage)) = self.locals.iter().rev().find(|local| local.0 == name) {
+      return Ok((_type.clone(), _storage.clone()));
+    }
+    if self.enclosed(&name) {
+      return Err(Emitter::<W>::error(node, format!("{} : {}", PANIC_NONLOCAL, name)));
+    }
+    if let Some((_, _type, _storage)) = self.globals.iter().find(|global| global.0 == name) {
+      return Ok((_type.clone(), _storage.clone()));
+    }
+    // statements outside a program, as in the repl, have undeclared real variables
+    if !self.program {
+      return Ok((Type::REAL, Storage::LOCAL));
+    }
+    return Err(Emitter::<W>::error(node, format!("{} : {}", PANIC_VAR_NOT_DECLARAED, name)));
+  }
+  // error(&AST, String) -> Diagnostic
+  fn error(node: &AST, message: String) -> Diagnostic {
+    // at the node in the source, when the parser gave it a span
+    let mut diagnostic = Diagnostic::new(message);
+    diagnostic.span = node.span;
+    return diagnostic;
+  }
+  // enclosed(&str) -> bool
+  fn enclosed(&self, key: &str) -> bool {
+    // a variable of an enclosing routine, which is not in the function emitted here
+    return self.enclosing.iter().flatten().any(|enclosed| enclosed == key);
+  }
+  // address(&AST, &Storage) -> Option<String>
+  fn address(&self, node: &AST, storage: &Storage) -> Option<String> {
+    // an instruction giving the address of a variable in memory
+    match storage {
+      Storage::STATIC(_address) => return Some(format!("({}{} {})", NTYPE_INTEGER, WASM_CONSTANT, _address)),
+      Storage::FRAME(_offset) => return Some(format!("({}{} ({} {}) ({}{} {}))", NTYPE_INTEGER, WASM_PLUS, WASM_VARIABLE, names::internal(&self.scopes, FRAME_POINTER), NTYPE_INTEGER, WASM_CONSTANT, _offset)),
+      Storage::REFERENCE | Storage::CONSTANT => return Some(format!("({} {})", WASM_VARIABLE, self.mangle(node, false))),
+      Storage::ARRAY(_first, _, _) => return self.address(node, _first),
+      Storage::GLOBAL | Storage::LOCAL => return None
+    }
+  }
+  // parameters(&AST) -> Result<Vec<(AST, Type, Storage)>, Diagnostic>
+  fn parameters(node: &AST) -> Result<Vec<(AST, Type, Storage)>, Diagnostic> {
+    // groups of parameters have their type as token, in a VAR or CONST node when passed by reference
+    let mut parameters = vec![];
+    for _group in &node.children {
+      let (_group, storage) = match (&_group.token, _group.children.first()) {
+        (Token::VAR, Some(_types)) => (_types, Storage::REFERENCE),
+        (Token::CONST, Some(_types)) => (_types, Storage::CONSTANT),
+        _ => (_group, Storage::LOCAL)
+      };
+      match &_group.token {
+        Token::TYPE_SPEC(_type) => parameters.extend(_group.children.iter().map(|_id| (_id.clone(), _type.clone(), storage.clone()))),
+        _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, _group)))
+      }
+    }
+    return Ok(parameters);
+  }
+  // flatten(&AST) -> Vec<&AST>
+  fn flatten(node: &AST) -> Vec<&AST> {
+    let mut nodes = vec![node];
+    for _child in &node.children {
+      nodes.extend(Emitter::<W>::flatten(_child));
+    }
+    return nodes;
+  }
+  // references(&AST) -> Result<Vec<(String, usize, Storage)>, Diagnostic>
+  fn references(tree: &AST) -> Result<Vec<(String, usize, Storage)>, Diagnostic> {
+    // parameters passed by reference, by name of the routine and position
+    let mut references = vec![];
+    for _node in Emitter::<W>::flatten(tree) {
+      if _node.token == Token::PROCEDURE || _node.token == Token::FUNCTION {
+        for (_index, (_, _, _storage)) in Emitter::<W>::parameters(&_node.children[1])?.into_iter().enumerate() {
+          if _storage != Storage::LOCAL {
+            references.push((Emitter::<W>::key(&_node.children[0]), _index, _storage));
+          }
+        }
+      }
+    }
+    return Ok(references);
+  }
+  // statics(&AST) -> Result<i64, Diagnostic>
+  fn statics(&self, block: &AST) -> Result<i64, Diagnostic> {
+    // bytes for the globals in memory, which must leave room for the shadow stack in the largest memory
+    let mut statics = 0;
+    for _group in block.children.iter().filter(|child| child.token == Token::VAR).flat_map(|declaration| declaration.children.iter()) {
+      for _id in &_group.children {
+        statics += match _id.children.first() {
+          Some(_range) => {
+            let (low, high) = Emitter::<W>::bounds(_range)?;
+            (high - low + 1) * SLOT
+          },
+          None if self.addressed.contains(&Emitter::<W>::key(_id)) => SLOT,
+          None => 0
+        };
+        if statics + STACK_SIZE > PAGES * PAGE {
+          return Err(Emitter::<W>::error(_id, format!("{} : {}", PANIC_MEMORY, Emitter::<W>::key(_id))));
+        }
+      }
+    }
+    return Ok(statics);
+  }
+  // addressed(&AST, &[(String, usize, Storage)]) -> Vec<String>
+  fn addressed(tree: &AST, references: &[(String, usize, Storage)]) -> Vec<String> {
+    // variables passed to a var parameter of a routine of that name anywhere, which can be more than needed but never less
+    let mut addressed = vec![];
+    for _node in Emitter::<W>::flatten(tree) {
+      if _node.token == Token::CALL {
+        let name = Emitter::<W>::key(&_node.children[0]);
+        for (_index, _argument) in _node.children[1..].iter().enumerate() {
+          if let Token::ID(_) = _argument.token {
+            if references.contains(&(name.clone(), _index, Storage::REFERENCE)) {
+              addressed.push(Emitter::<W>::key(_argument));
+            }
+          }
+        }
+      }
+    }
+    return addressed;
+  }
+  // routine(&str) -> Option<(String, Parameters, Option<Type>)>
+  fn routine(&self, name: &str) -> Option<(String, Parameters, Option<Type>)> {
+    // nested routines are after the routines they are declared in
+    match self.routines.iter().rev().find(|routine| routine.0 == name) {
+      Some((_, _mangled, _parameters, _result)) => return Some((_mangled.clone(), _parameters.clone(), _result.clone())),
+      None => return None
+    }
+  }
+  // result() -> AST
+  fn result() -> AST {
+    // the result is named by the compiler, so a variable called result is another local
+    return AST::new(Token::ID(names::internal(&[], RESULT)), vec![]);
+  }
+  // resolve(&AST, bool) -> AST
+  fn resolve(&self, node: &AST, assigned: bool) -> AST {
+    // a name alone is a variable, the result inside a function when it is Result or the function assigned to, or a call without arguments
+    if let (Token::ID(_string), true) = (&node.token, node.children.is_empty()) {
+      let name = names::canonical(_string);
+      let local = self.locals.iter().any(|local| local.0 == name);
+      if !local && self.function.is_some() && (name == RESULT || (assigned && self.function.as_ref() == Some(&name))) {
+        return Emitter::<W>::result();
+      }
+      let variable = local || self.globals.iter().any(|global| global.0 == name);
+      if !variable && self.routine(&name).is_some() {
+        return AST { token: Token::CALL, children: vec![node.clone()], span: node.span };
+      }
+    }
+    return node.clone();
+  }
+  // writes(&AST) -> bool
+  fn writes(node: &AST) -> bool {
+    // WRITELN is used somewhere in node, so console.log is imported
+    if let (Token::CALL, Some(_name)) = (&node.token, node.children.first()) {
+      if Emitter::<W>::key(_name) == WRITELN {
+        return true;
+      }
+    }
+    return node.children.iter().any(|child| Emitter::<W>::writes(child));
+  }
+  // literal(&Token) -> String
+  fn literal(token: &Token) -> String {
+    match token {
+      Token::INTEGER(_int) => return format!("{}", _int),
+      Token::REAL(_float) => return format!("{:?}", _float),
+      Token::TRUE => return "true".to_string(),
+      Token::FALSE => return "false".to_string(),
+      _ => return format!("{:?}", token)
+    }
+  }
+  // mangle(&AST, bool) -> String
+  fn mangle(&self, node: &AST, global: bool) -> String {
+    // globals are declared outside every function
+    let scopes = if global { vec![] } else { self.scopes.clone() };
+    match (&node.token, node.children.first().map(|child| &child.token)) {
+      (Token::ID(_string), _) if *_string == names::internal(&[], RESULT) => return names::internal(&scopes, RESULT),
+      (Token::ID(_string), _) => return names::mangle(&scopes, _string),
+      _ => return names::mangle(&scopes, &format!("{:?}", node.token))
+    }
+  }
+  // type_of(&AST) -> Result<Type, Diagnostic>
+  fn type_of(&self, node: &AST) -> Result<Type, Diagnostic> {
+    let mismatch = || Emitter::<W>::error(node, format!("{} : {:?}", PANIC_TYPE_MISMATCH, node));
+    match &node.token {
+      Token::INTEGER(_) => return Ok(Type::INTEGER),
+      Token::REAL(_) => return Ok(Type::REAL),
+      Token::TRUE | Token::FALSE => return Ok(Type::BOOLEAN),
+      Token::ID(_string) => {
+        let node = self.resolve(node, false);
+        if node.token == Token::CALL {
+          return self.type_of(&node);
+        }
+        if Emitter::<W>::indexed(&node) {
+          return Ok(self.entry(&node)?.0);
+        }
+        return Ok(self.lookup(&node)?.0);
+      },
+      // procedures have no value
+      Token::CALL => {
+        let name = Emitter::<W>::key(&node.children[0]);
+        match self.routine(&name) {
+          Some((_, _, Some(_type))) => return Ok(_type),
+          Some(_) => return Err(mismatch()),
+          None if name == WRITELN => return Err(mismatch()),
+          None => return Err(Emitter::<W>::error(node, format!("{} : {}", PANIC_ROUTINE_NOT_DECLARED, name)))
+        }
+      },
+      Token::PLUS | Token::MINUS if node.children.len() == 1 => {
+        match self.type_of(&node.children[0])? {
+          Type::BOOLEAN => return Err(mismatch()),
+          _type => return Ok(_type)
+        }
+      },
+      _ => {}
+    }
+    if node.children.len() != 2 {
+      return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)));
+    }
+    let left = self.type_of(&node.children[0])?;
+    let right = self.type_of(&node.children[1])?;
+    let numeric = left != Type::BOOLEAN && right != Type::BOOLEAN;
+    match node.token {
+      Token::PLUS | Token::MINUS | Token::MULTIPLY if numeric => {
+        // integers are widened to real when mixed with reals
+        if left == Type::INTEGER && right == Type::INTEGER { return Ok(Type::INTEGER); }
+        return Ok(Type::REAL);
+      },
+      Token::DIVIDE if numeric => return Ok(Type::REAL),
+      Token::INTEGER_DIV | Token::INTEGER_MOD if left == Type::INTEGER && right == Type::INTEGER => return Ok(Type::INTEGER),
+      Token::EQUAL | Token::NOT_EQUAL | Token::GREATER_THAN | Token::GREATER_EQUAL | Token::LESS_THAN | Token::LESS_EQUAL if numeric || left == right => return Ok(Type::BOOLEAN),
+      _ => return Err(mismatch())
+    }
+  }
+  // visit_expression(&AST, &Type)
+  fn visit_expression(&mut self, node: &AST, _type: &Type) -> Result<(), Diagnostic> {
+    // emit node as a value of _type, integers are converted where a real is expected
+    match (self.type_of(node)?, _type, &node.token) {
+      (Type::INTEGER, Type::REAL, Token::INTEGER(_int)) => {
+        if self.verbose { self.writer.write_all(format!("{}{};; number", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
+        self.writer.write_all(format!("{}{}({}{} {:?})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_CONSTANT, _int).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+      },
+      (Type::INTEGER, Type::REAL, _) => {
+        self.visit_value(node)?;
+        self.writer.write_all(format!("{}{}({}{}_{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_CONVERT, NTYPE_INTEGER, WASM_SIGNED).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+      },
+      (_from, _to, _) if _from == *_to => {
+        self.visit_value(node)?;
+      },
+      (_from, _to, _) => return Err(Emitter::<W>::error(node, format!("{} : {:?} : {:?} to {:?}", PANIC_TYPE_MISMATCH, node.token, _from, _to)))
+    }
+    return Ok(());
+  }
+  // visit_value(&AST)
+  fn visit_value(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    // the result of a call in an expression is kept
+    match &node.token {
+      Token::CALL => return self.visit_call(node, true),
+      _ => return self.visit(node)
+    }
+  }
   // visit_number
-  fn visit_number(&mut self, node: &AST) {
-    if OUTPUT_VERBOSE { self.file.write_all(format!("{}{};; number", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE); };
+  fn visit_number(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    if self.verbose { self.writer.write_all(format!("{}{};; number", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
     match node.token {
+      Token::INTEGER(_int) if i32::try_from(_int).is_err() => return Err(Emitter::<W>::error(node, format!("{} : {}", PANIC_INTEGER_RANGE, _int))),
       Token::INTEGER(_int) => {
-        match self.file.write_all(format!("{}{}({}{} {:?})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_CONSTANT, _int).as_bytes()) {
-          Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
+        match self.writer.write_all(format!("{}{}({}{} {:?})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_CONSTANT, _int).as_bytes()) {
+          Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
           Ok(_) => {},
         }
       },
       Token::REAL(_float) => {
-        match self.file.write_all(format!("{}{}({}{} {:?})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_CONSTANT, _float).as_bytes()) {
-          Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
+        match self.writer.write_all(format!("{}{}({}{} {:?})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_CONSTANT, _float).as_bytes()) {
+          Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
           Ok(_) => {},
         }
       },
-      _ => panic!("{} : {:?}", PANIC_COMPILE, node)
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
     }
+    return Ok(());
   }
   // visit_boolean
-  // fn visit_boolean(&mut self, node: &AST) {
-  //   if OUTPUT_VERBOSE { self.file.write_all(format!("{}{};; boolean", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE); };
-  //   match node.token {
-  //     Token::TRUE => {
-  //       match self.file.write_all(format!("{}{}({}{} {:?})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_CONSTANT, 1).as_bytes()) {
-  //         Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-  //         Ok(_) => {},
-  //       }
-  //     },
-  //     Token::FALSE => {
-  //       match self.file.write_all(format!("{}{}({}{} {:?})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_CONSTANT, 0).as_bytes()) {
-  //         Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-  //         Ok(_) => {},
-  //       }
-  //     },
-  //     _ => panic!("{} : {:?}", PANIC_COMPILE, node)
-  //   }
-  // }
+  fn visit_boolean(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    if self.verbose { self.writer.write_all(format!("{}{};; boolean", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
+    let value = match node.token {
+      Token::TRUE => 1,
+      Token::FALSE => 0,
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
+    };
+    match self.writer.write_all(format!("{}{}({}{} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_CONSTANT, value).as_bytes()) {
+      Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
+      Ok(_) => {},
+    }
+    return Ok(());
+  }
+  // visit_comment
+  fn visit_comment(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    match &node.token {
+      Token::COMMENT(_text) => {
+        // each line of a Pascal comment becomes a line comment
+        for _line in _text.lines() {
+          self.writer.write_all(format!("{}{};; {}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), _line.trim()).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+        }
+      },
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
+    }
+    return Ok(());
+  }
   // visit_unary_operator
-  fn visit_unary_operator(&mut self, node: &AST) {
-    self.visit(&node.children[0]);
-    if OUTPUT_VERBOSE { self.file.write_all(format!("{}{};; unary operator", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE); };
-    match node.token {
-      // Token::PLUS => {
-      // },
-      Token::MINUS => {
-        match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_NEGATION).as_bytes()) {
-          Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
+  fn visit_unary_operator(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    let _type = self.type_of(node)?;
+    self.visit_expression(&node.children[0], &_type)?;
+    if self.verbose { self.writer.write_all(format!("{}{};; unary operator", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
+    match (&node.token, &_type) {
+      (Token::PLUS, _) => {},
+      (Token::MINUS, Type::REAL) => {
+        match self.writer.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_NEGATION).as_bytes()) {
+          Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
+          Ok(_) => {},
+        }
+      },
+      // i32 has no negation
+      (Token::MINUS, _) => {
+        match self.writer.write_all(format!("{}{}({}{} -1){}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_CONSTANT, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_MULTIPLY).as_bytes()) {
+          Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
           Ok(_) => {},
         }
       },
-      _ => panic!("{} : {:?}", PANIC_COMPILE, node)
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
     }
+    return Ok(());
   }
   // visit_binary_operator
-  fn visit_binary_operator(&mut self, node: &AST) {
+  fn visit_binary_operator(&mut self, node: &AST) -> Result<(), Diagnostic> {
     if node.children.len() as i32 != 2 {
-      self.visit_unary_operator(node);
+      self.visit_unary_operator(node)?;
     } else {
-      // type checker for integer operators
-      match node.token {
-        // Token::INTEGER_DIV | Token::INTEGER_MOD => {
-        //   // first child, convert f64 to i32
-        //   self.visit(&node.children[0]);
-        //   match self.file.write_all(format!("{}{}({}{}_{}_s)", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_TRUNCATE, NTYPE_REAL).as_bytes()) {
-        //     Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-        //     Ok(_) => {},
-        //   }
-        //   // second child, convert f64 to i32
-        //   self.visit(&node.children[1]);
-        //   match self.file.write_all(format!("{}{}({}{}_{}_s)", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_TRUNCATE, NTYPE_REAL).as_bytes()) {
-        //     Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-        //     Ok(_) => {},
-        //   }
-        // }
-        // otherwise, visit children
-        _ => {
-          self.visit(&node.children[0]);
-          self.visit(&node.children[1]);
-        }
-      }
-      if OUTPUT_VERBOSE { self.file.write_all(format!("{}{};; binary operator", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE); };
-      // match operator
-      match node.token {
-        Token::PLUS => {
-          match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_PLUS).as_bytes()) {
-            Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-            Ok(_) => {},
-          }
-        },
-        Token::MINUS => {
-          match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_MINUS).as_bytes()) {
-            Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-            Ok(_) => {},
-          }
-        },
-        Token::MULTIPLY => {
-          match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_MULTIPLY).as_bytes()) {
-            Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-            Ok(_) => {},
-          }
-        },
-        Token::DIVIDE => {
-          match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_DIVIDE).as_bytes()) {
-            Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-            Ok(_) => {},
-          }
-        },
-        // Token::INTEGER_DIV => {
-        //   if !self.require_i32 {
-        //     match self.file.write_all(format!("{}{}({}{}){}{}({}{}_{}_s)", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_INTEGER_DIV, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_CONVERT, NTYPE_INTEGER).as_bytes()) {
-        //       Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-        //       Ok(_) => {},
-        //     }
-        //   } else {
-        //     match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_INTEGER_DIV).as_bytes()) {
-        //       Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-        //       Ok(_) => {},
-        //     }
-        //     self.require_i32 = false;
-        //   }
-        // },
-        Token::INTEGER_MOD => {
-          if !self.require_i32 {
-            match self.file.write_all(format!("{}{}({}{}){}{}({}{}_{}_s)", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_INTEGER_MOD, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_CONVERT, NTYPE_INTEGER).as_bytes()) {
-              Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-              Ok(_) => {},
-            }
-          } else {
-            match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_INTEGER_MOD).as_bytes()) {
-              Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-              Ok(_) => {},
-            }
-            self.require_i32 = false;
-          }
-        },
-        Token::EQUAL => {
-          match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_EQUAL).as_bytes()) {
-            Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-            Ok(_) => {},
-          }
-        },
-        Token::GREATER_THAN => {
-          match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_GREATER_THAN).as_bytes()) {
-            Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-            Ok(_) => {},
-          }
-        },
-        // Token::GREATER_EQUAL => {
-        //   match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_GREATER_EQUAL).as_bytes()) {
-        //     Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-        //     Ok(_) => {},
-        //   }
-        // },
-        Token::LESS_THAN => {
-          match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_LESS_THAN).as_bytes()) {
-            Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-            Ok(_) => {},
+      // operands have the type of the result, except for comparisons and division
+      let _type = match (&node.token, self.type_of(node)?) {
+        (Token::DIVIDE, _) => Type::REAL,
+        (_, Type::BOOLEAN) => {
+          match (self.type_of(&node.children[0])?, self.type_of(&node.children[1])?) {
+            (Type::REAL, _) | (_, Type::REAL) => Type::REAL,
+            (_left, _) => _left
           }
         },
-        // Token::LESS_EQUAL => {
-        //   match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_LESS_EQUAL).as_bytes()) {
-        //     Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-        //     Ok(_) => {},
-        //   }
-        // },
-        // Token::NOT_EQUAL => {
-        //   match self.file.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_REAL, WASM_NOT_EQUAL).as_bytes()) {
-        //     Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-        //     Ok(_) => {},
-        //   }
-        // },
-        _ => panic!("{} : {:?}", PANIC_COMPILE, node)
+        (_, _type) => _type
+      };
+      self.visit_expression(&node.children[0], &_type)?;
+      self.visit_expression(&node.children[1], &_type)?;
+      if self.verbose { self.writer.write_all(format!("{}{};; binary operator", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
+      // match operator
+      let opcode = match node.token {
+        Token::PLUS => WASM_PLUS,
+        Token::MINUS => WASM_MINUS,
+        Token::MULTIPLY => WASM_MULTIPLY,
+        Token::DIVIDE => WASM_DIVIDE,
+        Token::INTEGER_DIV => WASM_INTEGER_DIV,
+        Token::INTEGER_MOD => WASM_INTEGER_MOD,
+        Token::EQUAL => WASM_EQUAL,
+        Token::NOT_EQUAL => WASM_NOT_EQUAL,
+        Token::GREATER_THAN => WASM_GREATER_THAN,
+        Token::GREATER_EQUAL => WASM_GREATER_EQUAL,
+        Token::LESS_THAN => WASM_LESS_THAN,
+        Token::LESS_EQUAL => WASM_LESS_EQUAL,
+        _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
+      };
+      // i32 comparisons are signed
+      let signed = match (&_type, &node.token) {
+        (Type::REAL, _) => "",
+        (_, Token::GREATER_THAN) | (_, Token::GREATER_EQUAL) | (_, Token::LESS_THAN) | (_, Token::LESS_EQUAL) => WASM_SIGNED,
+        _ => ""
+      };
+      match self.writer.write_all(format!("{}{}({}{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), Emitter::<W>::ntype(&_type), opcode, signed).as_bytes()) {
+        Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
+        Ok(_) => {},
       }
     }
+    return Ok(());
+  }
+  // load(&AST) -> Result<String, Diagnostic>
+  fn load(&self, node: &AST) -> Result<String, Diagnostic> {
+    // the instruction reading a variable
+    let (_type, storage) = self.lookup(node)?;
+    match (self.address(node, &storage), &storage) {
+      (Some(_address), _) => return Ok(format!("({}{} {})", Emitter::<W>::ntype(&_type), WASM_LOAD, _address)),
+      (None, Storage::GLOBAL) => return Ok(format!("({} {})", WASM_GLOBAL_VARIABLE, self.mangle(node, true))),
+      (None, _) => return Ok(format!("({} {})", WASM_VARIABLE, self.mangle(node, false)))
+    }
+  }
+  // store(&AST) -> Result<(Type, String), Diagnostic>
+  fn store(&self, node: &AST) -> Result<(Type, String), Diagnostic> {
+    // the type of a variable, and the start of the instruction writing it, with the value to follow
+    let (_type, storage) = self.lookup(node)?;
+    match (self.address(node, &storage), &storage) {
+      (_, Storage::CONSTANT) => return Err(Emitter::<W>::error(node, format!("{} : {:?}", PANIC_CONSTANT_ASSIGNMENT, node.token))),
+      (Some(_address), _) => return Ok((_type.clone(), format!("{}{} {}", Emitter::<W>::ntype(&_type), WASM_STORE, _address))),
+      (None, Storage::GLOBAL) => return Ok((_type, format!("{} {}", WASM_GLOBAL_ASSIGNMENT, self.mangle(node, true)))),
+      (None, _) => return Ok((_type, format!("{} {}", WASM_ASSIGNMENT, self.mangle(node, false))))
+    }
   }
   // visit_variable
-  fn visit_variable(&mut self, node: &AST) {
-    if OUTPUT_VERBOSE { self.file.write_all(format!("{}{};; variable reference", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE); };
+  fn visit_variable(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    if self.verbose { self.writer.write_all(format!("{}{};; variable reference", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
     match &node.token {
+      // array elements have their index as child
       Token::ID(_string) => {
-        // indexed variables
-        if node.children.len() as i32 > 0 {
-          match node.children[0].token {
-            Token::INTEGER(_int) => {
-              match self.file.write_all(format!("{}{}({} ${}_{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_VARIABLE, _string, _int).as_bytes()) {
-                Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-                Ok(_) => {},
-              }
-            },
-            _ => panic!("{} : {:?}", PANIC_COMPILE, node)
-          }
-        } else {
-          match self.file.write_all(format!("{}{}({} ${})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_VARIABLE, _string).as_bytes()) {
-            Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-            Ok(_) => {},
-          }
+        let node = &self.resolve(node, false);
+        if node.token == Token::CALL {
+          return self.visit_call(node, true);
+        }
+        if Emitter::<W>::indexed(node) {
+          return self.visit_element(node, None);
+        }
+        let instruction = self.load(node)?;
+        match self.writer.write_all(format!("{}{}{}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), instruction).as_bytes()) {
+          Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
+          Ok(_) => {},
         }
       }
-      _ => panic!("{} : {:?}", PANIC_COMPILE, node)
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
     }
+    return Ok(());
   }
   // visit_while
-  fn visit_while(&mut self, node: &AST) {
-    if OUTPUT_VERBOSE { self.file.write_all(format!("{}{};; while statement", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE); };
+  fn visit_while(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    if self.verbose { self.writer.write_all(format!("{}{};; while statement", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
     match &node.token {
       Token::WHILE => {
-        match self.file.write_all(format!("{}{}({}{}{}({}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_BLOCK, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize + 1), WASM_LOOP).as_bytes()) {
-          Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
+        match self.writer.write_all(format!("{}{}({}{}{}({}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_BLOCK, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize + 1), WASM_LOOP).as_bytes()) {
+          Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
           Ok(_) => {
-            // statement
             self.tab_pos += 2;
-            self.visit(&node.children[1]);
-            if OUTPUT_VERBOSE { self.file.write_all(format!("{}{};; conditional statement", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE); };
-            match self.file.write_all(format!("{}{}({} 1", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_BREAK_IF).as_bytes()) {
-              Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
+            // leave the loop before the statement when the condition is false
+            if self.verbose { self.writer.write_all(format!("{}{};; conditional statement", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
+            match self.writer.write_all(format!("{}{}({} 1", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_BREAK_IF).as_bytes()) {
+              Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
               Ok(_) => {
                 self.tab_pos += 1;
                 // swap booleans to work as condition in while loop
@@ -251,28 +608,28 @@ impl Emitter {
                 match token {
                   Token::EQUAL => {
                     let expression = AST::new(Token::NOT_EQUAL, children);
-                    self.visit(&expression);
+                    self.visit(&expression)?;
                   },
                   Token::GREATER_THAN => {
                     let expression = AST::new(Token::LESS_EQUAL, children);
-                    self.visit(&expression);
+                    self.visit(&expression)?;
+                  },
+                  Token::GREATER_EQUAL => {
+                    let expression = AST::new(Token::LESS_THAN, children);
+                    self.visit(&expression)?;
                   },
-                  // Token::GREATER_EQUAL => {
-                  //   let expression = AST::new(Token::LESS_THAN, children);
-                  //   self.visit(&expression);
-                  // },
                   Token::LESS_THAN => {
                     let expression = AST::new(Token::GREATER_EQUAL, children);
-                    self.visit(&expression);
+                    self.visit(&expression)?;
+                  },
+                  Token::LESS_EQUAL => {
+                    let expression = AST::new(Token::GREATER_THAN, children);
+                    self.visit(&expression)?;
+                  },
+                  Token::NOT_EQUAL => {
+                    let expression = AST::new(Token::EQUAL, children);
+                    self.visit(&expression)?;
                   },
-                  // Token::LESS_EQUAL => {
-                  //   let expression = AST::new(Token::GREATER_THAN, children);
-                  //   self.visit(&expression);
-                  // },
-                  // Token::NOT_EQUAL => {
-                  //   let expression = AST::new(Token::EQUAL, children);
-                  //   self.visit(&expression);
-                  // },
                   // Token::TRUE => {
                   //   let expression = AST::new(Token::FALSE, vec![]);
                   //   self.visit(&expression);
@@ -281,298 +638,921 @@ impl Emitter {
                   //   let expression = AST::new(Token::TRUE, vec![]);
                   //   self.visit(&expression);
                   // },
-                  _ => {}
+                  // any other boolean
+                  _ => {
+                    self.visit_expression(&node.children[0], &Type::BOOLEAN)?;
+                    self.writer.write_all(format!("{}{}({}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), NTYPE_INTEGER, WASM_EQUAL_ZERO).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+                  }
                 }
                 self.tab_pos -= 1;
-                self.file.write_all(format!("{}{}){}{}({} 0){}{}){}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_BREAK, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 2)).as_bytes()).expect(PANIC_WRITE);
+                self.writer.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+                // statement
+                self.visit(&node.children[1])?;
+                self.writer.write_all(format!("{}{}({} 0){}{}){}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_BREAK, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 2)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
                 self.tab_pos -= 2;
               }
             }
           }
         }
       },
-      _ => panic!("{} : {:?}", PANIC_COMPILE, node)
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
     }
+    return Ok(());
   }
   // visit_if
-  fn visit_if(&mut self, node: &AST) {
-    if OUTPUT_VERBOSE { self.file.write_all(format!("{}{};; if statement", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE); };
+  fn visit_if(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    if self.verbose { self.writer.write_all(format!("{}{};; if statement", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
     match &node.token {
       Token::IF => {
-        self.require_i32 = true;
-        match self.file.write_all(format!("{}{}({}{}{}({}{}{}({} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_IF, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize + 1), WASM_BLOCK, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize + 2), WASM_RESULT, NTYPE_INTEGER).as_bytes()) {
-          Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
+        match self.writer.write_all(format!("{}{}({}{}{}({}{}{}({} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_IF, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize + 1), WASM_BLOCK, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize + 2), WASM_RESULT, NTYPE_INTEGER).as_bytes()) {
+          Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
           Ok(_) => {
-            // statement
+            // condition
             self.tab_pos += 2;
-            self.visit(&node.children[0]);
-            //if OUTPUT_VERBOSE { self.file.write_all(format!("{}{}){}{};; conditional statement", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1)).as_bytes()).expect(PANIC_WRITE); };
+            self.visit_expression(&node.children[0], &Type::BOOLEAN)?;
+            //if self.verbose { self.writer.write_all(format!("{}{}){}{};; conditional statement", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1)).as_bytes()).expect(PANIC_WRITE); };
             // expression
-            match self.file.write_all(format!("{}{}){}{}({}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1), WASM_THEN).as_bytes()) {
-              Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
+            match self.writer.write_all(format!("{}{}){}{}({}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1), WASM_THEN).as_bytes()) {
+              Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
               Ok(_) => {
                 //self.tab_pos += 1;
                 let token = node.children[1].token.clone();
                 let children = node.children[1].children.clone();
                 // expression
                 let expression = AST::new(token, children);
-                self.visit(&expression);
+                self.visit(&expression)?;
                 //self.tab_pos -= 1;
-                self.file.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1)).as_bytes()).expect(PANIC_WRITE);
+                self.writer.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
                 //self.tab_pos -= 2;
               }
             }
             // else expression
             if node.children.len() as i32 == 3 {
-              match self.file.write_all(format!("{}{}({}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1), WASM_ELSE).as_bytes()) {
-                Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
+              match self.writer.write_all(format!("{}{}({}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1), WASM_ELSE).as_bytes()) {
+                Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
                 Ok(_) => {
                   //self.tab_pos += 1;
                   let token = node.children[2].token.clone();
                   let children = node.children[2].children.clone();
                   // expression
                   let expression = AST::new(token, children);
-                  self.visit(&expression);
+                  self.visit(&expression)?;
                   self.tab_pos -= 1;
-                  self.file.write_all(format!("{}{}){}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1)).as_bytes()).expect(PANIC_WRITE);
+                  self.writer.write_all(format!("{}{}){}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
                   self.tab_pos -= 1;
                 }
               }
+            } else {
+              self.tab_pos -= 2;
+              self.writer.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
             }
           }
         }
       },
-      _ => panic!("{} : {:?}", PANIC_COMPILE, node)
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
     }
+    return Ok(());
   }
   // visit_assign
-  fn visit_assign(&mut self, node: &AST) {
-    if OUTPUT_VERBOSE { self.file.write_all(format!("{}{};; assignment statement", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE); };
-    match &node.children[0].token {
+  fn visit_assign(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    if self.verbose { self.writer.write_all(format!("{}{};; assignment statement", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
+    // assigning to the name of a function sets its result
+    let variable = self.resolve(&node.children[0], true);
+    match &variable.token {
+      Token::ID(_string) if Emitter::<W>::indexed(&variable) => {
+        self.visit_element(&variable, Some(&node.children[1]))?;
+      },
+      // array elements have their index as child
       Token::ID(_string) => {
-        // indexed variables
-        if node.children[0].children.len() as i32 > 0 {
-          match node.children[0].children[0].token {
-            Token::INTEGER(_int) => {
-              match self.file.write_all(format!("{}{}({} ${}_{}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_ASSIGNMENT, _string, _int).as_bytes()) {
-                Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-                Ok(_) => {
-                  self.tab_pos += 1;
-                  self.visit(&node.children[1]);
-                  self.tab_pos -= 1;
-                  self.file.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE);
-                }
-              }
-            },
-            _ => panic!("{} : {:?}", PANIC_COMPILE, node)
-          }
-        // otherwise
-        } else {
-          match self.file.write_all(format!("{}{}({} ${}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_ASSIGNMENT, _string).as_bytes()) {
-            Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-            Ok(_) => {
-              self.tab_pos += 1;
-              self.visit(&node.children[1]);
-              self.tab_pos -= 1;
-              self.file.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE);
-            }
+        let (_type, instruction) = self.store(&variable)?;
+        match self.writer.write_all(format!("{}{}({}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), instruction).as_bytes()) {
+          Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
+          Ok(_) => {
+            self.tab_pos += 1;
+            self.visit_expression(&node.children[1], &_type)?;
+            self.tab_pos -= 1;
+            self.writer.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
           }
         }
       },
-      _ => panic!("{} : {:?}", PANIC_COMPILE, node)
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
     }
+    return Ok(());
+  }
+  // visit_element(&AST, Option<&AST>)
+  fn visit_element(&mut self, node: &AST, value: Option<&AST>) -> Result<(), Diagnostic> {
+    if self.verbose { self.writer.write_all(format!("{}{};; indexed variable", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
+    let (_type, _) = self.entry(node)?;
+    let temporary = names::internal(&self.scopes, Emitter::<W>::ntype(&_type));
+    // a value to assign is kept first, so elements read in it do not change the index
+    if let Some(_value) = value {
+      self.writer.write_all(format!("{}{}({} {}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_ASSIGNMENT, temporary).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+      self.tab_pos += 1;
+      self.visit_expression(_value, &_type)?;
+      self.tab_pos -= 1;
+      self.writer.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    }
+    let address = self.visit_index(node)?;
+    match value {
+      Some(_) => self.writer.write_all(format!("{}{}({}{} {} ({} {}))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), Emitter::<W>::ntype(&_type), WASM_STORE, address, WASM_VARIABLE, temporary).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?,
+      None => self.writer.write_all(format!("{}{}({}{} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), Emitter::<W>::ntype(&_type), WASM_LOAD, address).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?
+    }
+    return Ok(());
+  }
+  // visit_index(&AST) -> Result<String, Diagnostic>
+  fn visit_index(&mut self, node: &AST) -> Result<String, Diagnostic> {
+    // the index is kept in a local and an index outside the bounds is a trap, the element is at first + (index - low) * size
+    let (first, low, high) = match self.entry(node)? {
+      (_, Storage::ARRAY(_first, _low, _high)) => (*_first, _low, _high),
+      _ => return Err(Emitter::<W>::error(node, format!("{} : {}", PANIC_VAR_NOT_DECLARAED, Emitter::<W>::key(node))))
+    };
+    let index = names::internal(&self.scopes, INDEX);
+    self.writer.write_all(format!("{}{}({} {}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_ASSIGNMENT, index).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    self.tab_pos += 1;
+    self.visit_expression(&node.children[0], &Type::INTEGER)?;
+    self.tab_pos -= 1;
+    self.writer.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    let element = format!("({}{} ({} {}) ({}{} {}))", NTYPE_INTEGER, WASM_MINUS, WASM_VARIABLE, index, NTYPE_INTEGER, WASM_CONSTANT, low);
+    self.writer.write_all(format!("{}{}({} ({}{}{} {} ({}{} {})) ({} ({})))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_IF, NTYPE_INTEGER, WASM_GREATER_THAN, WASM_UNSIGNED, element, NTYPE_INTEGER, WASM_CONSTANT, high - low, WASM_THEN, WASM_UNREACHABLE).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    match self.address(node, &first) {
+      Some(_address) => return Ok(format!("({}{} {} ({}{} {} ({}{} {})))", NTYPE_INTEGER, WASM_PLUS, _address, NTYPE_INTEGER, WASM_MULTIPLY, element, NTYPE_INTEGER, WASM_CONSTANT, SLOT)),
+      None => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
+    }
+  }
+  // visit_temporaries(&[AST])
+  fn visit_temporaries(&mut self, body: &[AST]) -> Result<(), Diagnostic> {
+    // locals for elements with an index known only when running, declared with the other locals of the function
+    if !body.iter().any(|node| Emitter::<W>::flatten(node).into_iter().any(Emitter::<W>::indexed)) {
+      return Ok(());
+    }
+    for (_name, _ntype) in &[(INDEX, NTYPE_INTEGER), (NTYPE_INTEGER, NTYPE_INTEGER), (NTYPE_REAL, NTYPE_REAL)] {
+      self.writer.write_all(format!("{}{}({} {} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_LOCAL, names::internal(&self.scopes, _name), _ntype).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    }
+    return Ok(());
+  }
+  // overflow(i64) -> String
+  fn overflow(&self, bytes: i64) -> String {
+    // a trap when taking that many bytes from the shadow stack would cross into the globals below it
+    return format!("({} ({}{}{} ({} {}) ({}{} {})) ({} ({})))", WASM_IF, NTYPE_INTEGER, WASM_LESS_THAN, WASM_UNSIGNED, WASM_GLOBAL_VARIABLE, names::internal(&[], STACK_POINTER), NTYPE_INTEGER, WASM_CONSTANT, self.bottom + bytes, WASM_THEN, WASM_UNREACHABLE);
+  }
+  // write() -> String
+  fn write(&self) -> String {
+    // the instruction writing one number, with the number to follow
+    match self.target {
+      Target::WASI => return format!("{} {}", WASM_CALL, names::internal(&[], WRITELN)),
+      _ => return WASM_WRITE.to_string()
+    }
+  }
+  // visit_writeln
+  fn visit_writeln(&mut self) -> Result<(), Diagnostic> {
+    // the number is written backwards from the end of the buffer, with up to six decimals as console.log would, or NaN or Infinity
+    let lines = vec![
+      format!("({} {} ({} $value {})", WASM_FUNCTION, names::internal(&[], WRITELN), WASM_DECLARATION, NTYPE_REAL),
+      "  (local $buffer i32) (local $text i32) (local $int i64) (local $fraction i64) (local $digits i32) (local $negative i32)".to_string(),
+      format!("  {}", self.overflow(WASI_BUFFER)),
+      format!("  (global.set {sp} (i32.sub (global.get {sp}) (i32.const {size})))", sp = names::internal(&[], STACK_POINTER), size = WASI_BUFFER),
+      format!("  (local.set $buffer (global.get {}))", names::internal(&[], STACK_POINTER)),
+      format!("  (local.set $text (i32.add (local.get $buffer) (i32.const {})))", WASI_BUFFER - 1),
+      "  (i32.store8 (local.get $text) (i32.const 10))".to_string(),
+      "  (local.set $negative (f64.lt (local.get $value) (f64.const 0)))".to_string(),
+      "  (local.set $value (f64.abs (local.get $value)))".to_string(),
+      "  (block $done".to_string(),
+      "    (if (f64.ne (local.get $value) (local.get $value)) (then".to_string(),
+      "      (local.set $text (i32.sub (local.get $text) (i32.const 3)))".to_string(),
+      "      (i32.store16 (local.get $text) (i32.const 0x614e))".to_string(),
+      "      (i32.store8 offset=2 (local.get $text) (i32.const 0x4e))".to_string(),
+      "      (local.set $negative (i32.const 0))".to_string(),
+      "      (br $done)))".to_string(),
+      "    (if (f64.eq (local.get $value) (f64.const inf)) (then".to_string(),
+      "      (local.set $text (i32.sub (local.get $text) (i32.const 8)))".to_string(),
+      "      (i64.store (local.get $text) (i64.const 0x7974696e69666e49))".to_string(),
+      "      (br $done)))".to_string(),
+      "    (if (f64.lt (local.get $value) (f64.const 1e12))".to_string(),
+      "      (then".to_string(),
+      "        (local.set $int (i64.trunc_f64_u (f64.nearest (f64.mul (local.get $value) (f64.const 1e6)))))".to_string(),
+      "        (local.set $fraction (i64.rem_u (local.get $int) (i64.const 1000000)))".to_string(),
+      "        (local.set $int (i64.div_u (local.get $int) (i64.const 1000000))))".to_string(),
+      "      (else (local.set $int (i64.trunc_sat_f64_u (f64.nearest (local.get $value))))))".to_string(),
+      "    (if (i64.ne (local.get $fraction) (i64.const 0)) (then".to_string(),
+      "      (local.set $digits (i32.const 6))".to_string(),
+      "      (block $trimmed (loop $trim".to_string(),
+      "        (br_if $trimmed (i64.ne (i64.rem_u (local.get $fraction) (i64.const 10)) (i64.const 0)))".to_string(),
+      "        (local.set $fraction (i64.div_u (local.get $fraction) (i64.const 10)))".to_string(),
+      "        (local.set $digits (i32.sub (local.get $digits) (i32.const 1)))".to_string(),
+      "        (br $trim)))".to_string(),
+      "      (loop $decimals".to_string(),
+      "        (local.set $text (i32.sub (local.get $text) (i32.const 1)))".to_string(),
+      "        (i32.store8 (local.get $text) (i32.add (i32.const 48) (i32.wrap_i64 (i64.rem_u (local.get $fraction) (i64.const 10)))))".to_string(),
+      "        (local.set $fraction (i64.div_u (local.get $fraction) (i64.const 10)))".to_string(),
+      "        (local.set $digits (i32.sub (local.get $digits) (i32.const 1)))".to_string(),
+      "        (br_if $decimals (local.get $digits)))".to_string(),
+      "      (local.set $text (i32.sub (local.get $text) (i32.const 1)))".to_string(),
+      "      (i32.store8 (local.get $text) (i32.const 46))))".to_string(),
+      "    (loop $digits".to_string(),
+      "      (local.set $text (i32.sub (local.get $text) (i32.const 1)))".to_string(),
+      "      (i32.store8 (local.get $text) (i32.add (i32.const 48) (i32.wrap_i64 (i64.rem_u (local.get $int) (i64.const 10)))))".to_string(),
+      "      (local.set $int (i64.div_u (local.get $int) (i64.const 10)))".to_string(),
+      "      (br_if $digits (i64.ne (local.get $int) (i64.const 0)))))".to_string(),
+      "  (if (local.get $negative) (then".to_string(),
+      "    (local.set $text (i32.sub (local.get $text) (i32.const 1)))".to_string(),
+      "    (i32.store8 (local.get $text) (i32.const 45))))".to_string(),
+      "  (i32.store (local.get $buffer) (local.get $text))".to_string(),
+      format!("  (i32.store offset=4 (local.get $buffer) (i32.sub (i32.add (local.get $buffer) (i32.const {})) (local.get $text)))", WASI_BUFFER),
+      format!("  (drop (call {} (i32.const {}) (local.get $buffer) (i32.const 1) (i32.add (local.get $buffer) (i32.const 8))))", names::internal(&[], WASI_WRITE), WASI_STDOUT),
+      format!("  (global.set {sp} (i32.add (global.get {sp}) (i32.const {size})))", sp = names::internal(&[], STACK_POINTER), size = WASI_BUFFER),
+      ")".to_string(),
+    ];
+    for _line in lines {
+      self.writer.write_all(format!("{}{}{}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), _line).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    }
+    return Ok(());
+  }
+  // visit_call
+  fn visit_call(&mut self, node: &AST, value: bool) -> Result<(), Diagnostic> {
+    if self.verbose { self.writer.write_all(format!("{}{};; call", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
+    // the name is the first child, the arguments are the rest
+    let name = Emitter::<W>::key(&node.children[0]);
+    let arguments = &node.children[1..];
+    let (mangled, parameters, result) = match self.routine(&name) {
+      Some(_routine) => _routine,
+      // WRITELN writes each argument as a real
+      None if name == WRITELN => {
+        for _argument in arguments {
+          self.writer.write_all(format!("{}{}({}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), self.write()).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+          self.tab_pos += 1;
+          self.visit_expression(_argument, &Type::REAL)?;
+          self.tab_pos -= 1;
+          self.writer.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+        }
+        return Ok(());
+      },
+      None => return Err(Emitter::<W>::error(node, format!("{} : {}", PANIC_ROUTINE_NOT_DECLARED, name)))
+    };
+    if parameters.len() != arguments.len() {
+      return Err(Emitter::<W>::error(node, format!("{} : {} : {} for {}", PANIC_ARGUMENTS, name, arguments.len(), parameters.len())));
+    }
+    self.writer.write_all(format!("{}{}({} {}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_CALL, mangled).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    self.tab_pos += 1;
+    let stack_pointer = names::internal(&[], STACK_POINTER);
+    let mut temporaries = 0;
+    for (_argument, (_type, _storage)) in arguments.iter().zip(parameters.iter()) {
+      if *_storage == Storage::LOCAL {
+        self.visit_expression(_argument, _type)?;
+        continue;
+      }
+      // parameters passed by reference get the address of a variable of the same type
+      let variable = self.resolve(_argument, true);
+      let address = match &variable.token {
+        // an element with an index known only when running has its address worked out here
+        Token::ID(_string) if Emitter::<W>::indexed(&variable) => {
+          let (_from, _) = self.entry(&variable)?;
+          if _from != *_type && *_storage == Storage::REFERENCE {
+            return Err(Emitter::<W>::error(&variable, format!("{} : {:?} : {:?} to {:?}", PANIC_TYPE_MISMATCH, variable.token, _from, _type)));
+          }
+          if _from == *_type { Some(self.visit_index(&variable)?) } else { None }
+        },
+        Token::ID(_string) => {
+          let (_from, _from_storage) = self.lookup(&variable)?;
+          if _from_storage == Storage::CONSTANT && *_storage == Storage::REFERENCE {
+            return Err(Emitter::<W>::error(&variable, format!("{} : {:?}", PANIC_CONSTANT_ASSIGNMENT, variable.token)));
+          }
+          if _from != *_type && *_storage == Storage::REFERENCE {
+            return Err(Emitter::<W>::error(&variable, format!("{} : {:?} : {:?} to {:?}", PANIC_TYPE_MISMATCH, variable.token, _from, _type)));
+          }
+          if _from == *_type { self.address(&variable, &_from_storage) } else { None }
+        },
+        _ => None
+      };
+      match (address, _storage) {
+        (Some(_address), _) => {
+          self.writer.write_all(format!("{}{}{}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), _address).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+        },
+        (None, Storage::REFERENCE) => return Err(Emitter::<W>::error(_argument, format!("{} : {:?}", PANIC_VAR_ARGUMENT, _argument.token))),
+        // any other value for a const parameter is put on the stack until the call returns
+        (None, _) => {
+          temporaries += 1;
+          self.writer.write_all(format!("{}{}{}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), self.overflow(SLOT)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+          self.writer.write_all(format!("{}{}({} {} ({}{} ({} {}) ({}{} {})))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_GLOBAL_ASSIGNMENT, stack_pointer, NTYPE_INTEGER, WASM_MINUS, WASM_GLOBAL_VARIABLE, stack_pointer, NTYPE_INTEGER, WASM_CONSTANT, SLOT).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+          self.writer.write_all(format!("{}{}({}{} ({} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), Emitter::<W>::ntype(_type), WASM_STORE, WASM_GLOBAL_VARIABLE, stack_pointer).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+          self.tab_pos += 1;
+          self.visit_expression(_argument, _type)?;
+          self.tab_pos -= 1;
+          self.writer.write_all(format!("{}{}){}{}({} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_GLOBAL_VARIABLE, stack_pointer).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+        }
+      }
+    }
+    self.tab_pos -= 1;
+    self.writer.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    if temporaries > 0 {
+      self.writer.write_all(format!("{}{}({} {} ({}{} ({} {}) ({}{} {})))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_GLOBAL_ASSIGNMENT, stack_pointer, NTYPE_INTEGER, WASM_PLUS, WASM_GLOBAL_VARIABLE, stack_pointer, NTYPE_INTEGER, WASM_CONSTANT, temporaries * SLOT).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    }
+    // the result of a function called as a statement is not used
+    if !value && result.is_some() {
+      self.writer.write_all(format!("{}{}({})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_DROP).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    }
+    return Ok(());
   }
   // visit_constant_declaration
-  // fn visit_constant_declaration(&mut self, node: &AST) {
-  //   for _child in &node.children {
-  //     let token = &_child.token;
-  //     match token {
-  //       Token::INTEGER(_int) => {
-  //         for _id in &_child.children {
-  //           match &_id.token {
-  //             Token::ID(_string) => {
-  //               match self.file.write_all(format!("{}{}({} ${} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_DECLARATION, _string, NTYPE_REAL).as_bytes()) {
-  //                 Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-  //                 Ok(_) => {},
-  //               }
-  //             },
-  //             _ => panic!("{} : {:?}", PANIC_COMPILE, node)
-  //           }
-  //         }
-  //       },
-  //       Token::REAL(_float) => {
-  //         for _id in &_child.children {
-  //           match &_id.token {
-  //             Token::ID(_string) => {
-  //               match self.file.write_all(format!("{}{}({} ${} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_DECLARATION, _string, NTYPE_REAL).as_bytes()) {
-  //                 Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-  //                 Ok(_) => {},
-  //               }
-  //             },
-  //             _ => panic!("{} : {:?}", PANIC_COMPILE, node)
-  //           }
-  //         }
-  //       },
-  //       _ => {}
-  //     }
-  //   }
-  // }
+  fn visit_constant_declaration(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    // constants are put in place where they are used, so only their comments are kept
+    for _child in &node.children {
+      match (&_child.token, _child.children.first()) {
+        (Token::COMMENT(_text), _) => self.visit_comment(_child)?,
+        (_value, Some(_constant)) => {
+          if self.verbose { self.writer.write_all(format!("{}{};; constant {} = {}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), Emitter::<W>::key(_constant), Emitter::<W>::literal(_value)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
+        },
+        _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, _child)))
+      }
+    }
+    return Ok(());
+  }
   // visit_variable_declaration
-  // fn visit_variable_declaration(&mut self, node: &AST) {
-  //   for _child in &node.children {
-  //     let token = &_child.token;
-  //     match token {
-  //       Token::TYPE_SPEC(Type::INTEGER) => {
-  //         for _id in &_child.children {
-  //           match &_id.token {
-  //             Token::ID(_string) => {
-  //               // indexed variables
-  //               if _id.children.len() as i32 > 0 {
-  //                 for _child in &_id.children {
-  //                   match _child.token {
-  //                     Token::INTEGER(_int) => {
-  //                       match self.file.write_all(format!("{}{}({} ${}_{} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_DECLARATION, _string, _int, NTYPE_REAL).as_bytes()) {
-  //                         Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, token, why),
-  //                         Ok(_) => {},
-  //                       }
-  //                     },
-  //                     _ => panic!("{} : {:?}", PANIC_COMPILE, token),
-  //                   }
-  //                 }
-  //               // otherwise
-  //               } else {
-  //                 match self.file.write_all(format!("{}{}({} ${} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_DECLARATION, _string, NTYPE_REAL).as_bytes()) {
-  //                   Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-  //                   Ok(_) => {},
-  //                 }
-  //               }
-  //             },
-  //             _ => panic!("{} : {:?}", PANIC_COMPILE, node)
-  //           }
-  //         }
-  //       },
-  //       Token::TYPE_SPEC(Type::REAL) => {
-  //         for _id in &_child.children {
-  //           match &_id.token {
-  //             Token::ID(_string) => {
-  //               match self.file.write_all(format!("{}{}({} ${} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_DECLARATION, _string, NTYPE_REAL).as_bytes()) {
-  //                 Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-  //                 Ok(_) => {},
-  //               }
-  //             },
-  //             _ => panic!("{} : {:?}", PANIC_COMPILE, _id)
-  //           }
-  //         }
-  //       },
-  //       _ => {}
-  //     }
-  //   }
-  // }
+  fn visit_variable_declaration(&mut self, node: &AST, global: bool) -> Result<(), Diagnostic> {
+    if self.verbose { self.writer.write_all(format!("{}{};; variable declaration", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
+    // each declaration has the type as token and the declared variables as children, after its doc comments
+    for _child in &node.children {
+      let _type = match &_child.token {
+        Token::TYPE_SPEC(_type) => _type.clone(),
+        Token::COMMENT(_text) => {
+          self.visit_comment(_child)?;
+          continue;
+        },
+        _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, _child)))
+      };
+      for _id in &_child.children {
+        match (&_id.token, _id.children.first()) {
+          // arrays are in memory, one element after another, globals at the next address and locals in the frame
+          (Token::ID(_string), Some(_range)) => {
+            let (low, high) = Emitter::<W>::bounds(_range)?;
+            let size = (high - low + 1) * SLOT;
+            let key = Emitter::<W>::key(_id);
+            if global {
+              self.globals.push((key, _type.clone(), Storage::ARRAY(Box::new(Storage::STATIC(self.statics)), low, high)));
+              self.statics += size;
+            } else {
+              // a frame larger than the shadow stack would never fit
+              if self.frame + size > STACK_SIZE {
+                return Err(Emitter::<W>::error(_id, format!("{} : {}", PANIC_MEMORY, key)));
+              }
+              self.locals.push((key, _type.clone(), Storage::ARRAY(Box::new(Storage::FRAME(self.frame)), low, high)));
+              self.frame += size;
+            }
+          },
+          (Token::ID(_string), None) => {
+            let ntype = Emitter::<W>::ntype(&_type);
+            let key = Emitter::<W>::key(_id);
+            // globals are mutable and start as zero, as locals do, variables passed by reference are in memory instead
+            let declaration = match (global, self.addressed.contains(&key)) {
+              (true, false) => {
+                self.globals.push((key, _type.clone(), Storage::GLOBAL));
+                Some(format!("({} {} ({} {}) ({}{} 0))", WASM_GLOBAL, self.mangle(_id, true), WASM_MUTABLE, ntype, ntype, WASM_CONSTANT))
+              },
+              (true, true) => {
+                self.globals.push((key, _type.clone(), Storage::STATIC(self.statics)));
+                self.statics += SLOT;
+                None
+              },
+              (false, false) => {
+                self.locals.push((key, _type.clone(), Storage::LOCAL));
+                Some(format!("({} {} {})", WASM_LOCAL, self.mangle(_id, false), ntype))
+              },
+              (false, true) => {
+                self.locals.push((key, _type.clone(), Storage::FRAME(self.frame)));
+                self.frame += SLOT;
+                None
+              }
+            };
+            if let Some(_declaration) = declaration {
+              match self.writer.write_all(format!("{}{}{}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), _declaration).as_bytes()) {
+                Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
+                Ok(_) => {},
+              }
+            }
+          },
+          _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, _id)))
+        }
+      }
+    }
+    return Ok(());
+  }
+  // visit_routine
+  fn visit_routine(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    if self.verbose { self.writer.write_all(format!("{}{};; {:?} declaration", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), node.token).to_lowercase().as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?; };
+    // procedures have the name, parameters and block as children, functions also have the type of the result before the block
+    let (name, block, result) = match (&node.token, &node.children[0].token, &node.children[2].token) {
+      (Token::PROCEDURE, Token::ID(_string), _) => (_string.clone(), &node.children[2], None),
+      (Token::FUNCTION, Token::ID(_string), Token::TYPE_SPEC(_type)) => (_string.clone(), &node.children[3], Some(_type.clone())),
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
+    };
+    let parameters = Emitter::<W>::parameters(&node.children[1])?;
+    // declared before the body, so it can call itself
+    let mangled = names::mangle(&self.scopes, &name);
+    self.routines.push((names::canonical(&name), mangled.clone(), parameters.iter().map(|parameter| (parameter.1.clone(), parameter.2.clone())).collect(), result.clone()));
+    let routines = self.routines.len();
+    // functions do not nest in wasm, so routines declared inside are emitted first and see none of the locals here
+    let mut enclosing: Vec<String> = parameters.iter().map(|parameter| Emitter::<W>::key(&parameter.0)).collect();
+    for _declaration in block.children.iter().filter(|child| child.token == Token::VAR) {
+      for _group in &_declaration.children {
+        enclosing.extend(_group.children.iter().map(|_id| Emitter::<W>::key(_id)));
+      }
+    }
+    self.enclosing.push(enclosing);
+    self.scopes.push(name.clone());
+    let locals = std::mem::take(&mut self.locals);
+    let function = self.function.take();
+    let frame = std::mem::replace(&mut self.frame, 0);
+    let mut body = vec![];
+    for _child in &block.children {
+      match _child.token {
+        Token::PROCEDURE | Token::FUNCTION => self.visit_routine(_child)?,
+        _ => body.push(_child.clone())
+      }
+    }
+    self.enclosing.pop();
+    // parameters are locals set by the caller, or the address of a variable when passed by reference
+    let mut signature = format!("({} {}", WASM_FUNCTION, mangled);
+    let mut copies = vec![];
+    for (_id, _type, _storage) in &parameters {
+      let ntype = if *_storage == Storage::LOCAL { Emitter::<W>::ntype(_type) } else { NTYPE_INTEGER };
+      signature.push_str(&format!(" ({} {} {})", WASM_DECLARATION, self.mangle(_id, false), ntype));
+      // value parameters passed on by reference are copied into the frame
+      let key = Emitter::<W>::key(_id);
+      if *_storage == Storage::LOCAL && self.addressed.contains(&key) {
+        copies.push((_id.clone(), _type.clone(), Storage::FRAME(self.frame)));
+        self.locals.push((key, _type.clone(), Storage::FRAME(self.frame)));
+        self.frame += SLOT;
+      } else {
+        self.locals.push((key, _type.clone(), _storage.clone()));
+      }
+    }
+    if let Some(_type) = &result {
+      signature.push_str(&format!(" ({} {})", WASM_RESULT, Emitter::<W>::ntype(_type)));
+    }
+    self.writer.write_all(format!("{}{}{}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), signature).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    self.tab_pos += 1;
+    // the result is a variable, returned at the end
+    let result_node = Emitter::<W>::result();
+    if let Some(_type) = &result {
+      let canonical = names::canonical(&name);
+      self.function = Some(canonical.clone());
+      if self.addressed.contains(&canonical) || self.addressed.contains(&RESULT.to_string()) {
+        self.locals.push((Emitter::<W>::key(&result_node), _type.clone(), Storage::FRAME(self.frame)));
+        self.frame += SLOT;
+      } else {
+        self.locals.push((Emitter::<W>::key(&result_node), _type.clone(), Storage::LOCAL));
+        self.writer.write_all(format!("{}{}({} {} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_LOCAL, self.mangle(&result_node, false), Emitter::<W>::ntype(_type)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+      }
+    }
+    // declarations, with the locals, come before the statements
+    let statements = body.iter().position(|child| child.token == Token::BLOCK).unwrap_or(body.len());
+    self.visit(&AST::new(Token::BLOCK, body[..statements].to_vec()))?;
+    self.visit_temporaries(&body)?;
+    // variables in memory are in a frame on the shadow stack, which starts as zero
+    let frame_pointer = names::internal(&self.scopes, FRAME_POINTER);
+    let stack_pointer = names::internal(&[], STACK_POINTER);
+    if self.frame > 0 {
+      self.writer.write_all(format!("{}{}({} {} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_LOCAL, frame_pointer, NTYPE_INTEGER).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+      self.writer.write_all(format!("{}{}{}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), self.overflow(self.frame)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+      self.writer.write_all(format!("{}{}({} {} ({}{} ({} {}) ({}{} {})))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_GLOBAL_ASSIGNMENT, stack_pointer, NTYPE_INTEGER, WASM_MINUS, WASM_GLOBAL_VARIABLE, stack_pointer, NTYPE_INTEGER, WASM_CONSTANT, self.frame).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+      self.writer.write_all(format!("{}{}({} {} ({} {}))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_ASSIGNMENT, frame_pointer, WASM_GLOBAL_VARIABLE, stack_pointer).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+      self.writer.write_all(format!("{}{}({} ({} {}) ({}{} 0) ({}{} {}))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_FILL, WASM_VARIABLE, frame_pointer, NTYPE_INTEGER, WASM_CONSTANT, NTYPE_INTEGER, WASM_CONSTANT, self.frame).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+      for (_id, _type, _storage) in &copies {
+        if let Some(_address) = self.address(_id, _storage) {
+          self.writer.write_all(format!("{}{}({}{} {} ({} {}))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), Emitter::<W>::ntype(_type), WASM_STORE, _address, WASM_VARIABLE, self.mangle(_id, false)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+        }
+      }
+    }
+    self.visit(&AST::new(Token::BLOCK, body[statements..].to_vec()))?;
+    if self.frame > 0 {
+      self.writer.write_all(format!("{}{}({} {} ({}{} ({} {}) ({}{} {})))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_GLOBAL_ASSIGNMENT, stack_pointer, NTYPE_INTEGER, WASM_PLUS, WASM_VARIABLE, frame_pointer, NTYPE_INTEGER, WASM_CONSTANT, self.frame).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    }
+    if result.is_some() {
+      self.visit_variable(&result_node)?;
+    }
+    self.tab_pos -= 1;
+    self.writer.write_all(format!("{}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    self.locals = locals;
+    self.function = function;
+    self.frame = frame;
+    self.scopes.pop();
+    self.routines.truncate(routines);
+    return Ok(());
+  }
   // visit_program
-  // fn visit_program(&mut self, node: &AST) {
-  //   // if DEBUG && DEBUG_SHOW_TREE { println!("{} {:?} {:?}", FORMAT_TAB.repeat(self.tab_pos as usize + 1), node.token, node.children[0].token); };
-  //   match &node.children[0].token {
-  //     Token::ID(_string) => {
-  //       match self.file.write_all(format!("{}({}{}{}(import \"console\" \"log\" (func $log (param f64))){}{}({}{}{};; signature{}{}({} \"{}\")", FORMAT_NEWLINE, WASM_MODULE, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize + 1), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize + 1), WASM_FUNCTION, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize + 2), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize + 2), WASM_EXPORT, _string).as_bytes()) {
-  //         Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-  //         Ok(_) => { 
-  //           self.tab_pos += 2;
-  //           // check if next is variable declaration
-  //           //println!("{:?}", &node.children[1].children[0].token);
-  //           match &node.children[1].children[0].token {
-  //             Token::VAR => {}
-  //             _ => {
-  //               // declare result type (if any)
-  //               // if self.parser.result_type {
-  //               //     //println!("{:?}", self.parser.result_type);
-  //               //     match self.file.write_all(format!("{}{}({} {}){}{};; body", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_RESULT, NTYPE_REAL, FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()) {
-  //               //         Err(why) => panic!("{} : {:?} : {}", PANIC_COMPILE, node, why),
-  //               //         Ok(_) => {},
-  //               //     }
-  //               // }
-  //             }
-  //           }
-  //           // visit next
-  //           self.visit(&node.children[1]);
-  //           self.tab_pos -= 1;
-  //           self.file.write_all(format!("{}{}){}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1)).as_bytes()).expect(PANIC_WRITE);
-  //           self.tab_pos -= 1;
-  //         }
-  //       }
-  //     },
-  //     _ => panic!("{} : {:?}", PANIC_COMPILE, node)
-  //   }
-  // }
+  fn visit_program(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    match &node.children[0].token {
+      Token::ID(_string) => {
+        self.program = true;
+        // the program is a function exported as the entry point
+        let export = match self.target {
+          Target::WASI => WASI_START.to_string(),
+          _ => names::canonical(_string)
+        };
+        match self.writer.write_all(format!("{}{}({}", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_MODULE).as_bytes()) {
+          Err(why) => return Err(Diagnostic::new(format!("{} : {:?} : {}", PANIC_COMPILE, node, why))),
+          Ok(_) => {
+            self.tab_pos += 1;
+            // imports are before everything else in a module, WASI has no console so writeln uses fd_write
+            let writes = Emitter::<W>::writes(node);
+            match (writes, self.target) {
+              (true, Target::WASI) => self.writer.write_all(format!("{}{}({} \"{}\" \"{}\" ({} {} ({} {} {} {} {}) ({} {})))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_IMPORT, WASI_MODULE, WASI_WRITE, WASM_FUNCTION, names::internal(&[], WASI_WRITE), WASM_DECLARATION, NTYPE_INTEGER, NTYPE_INTEGER, NTYPE_INTEGER, NTYPE_INTEGER, WASM_RESULT, NTYPE_INTEGER).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?,
+              (true, _) => self.writer.write_all(format!("{}{}({} \"{}\" \"{}\" ({} ${} ({} {})))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_IMPORT, IMPORT_MODULE, IMPORT_WRITE, WASM_FUNCTION, IMPORT_WRITE, WASM_DECLARATION, NTYPE_REAL).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?,
+              _ => {}
+            }
+            // memory for arrays and variables passed by reference, with the shadow stack at the end, WASI reads text from memory so it is exported there
+            if self.memory {
+              self.bottom = self.statics(&node.children[1])?;
+              let pages = (self.bottom + STACK_SIZE + PAGE - 1) / PAGE;
+              let export = if self.target == Target::WASI { format!(" ({} \"{}\")", WASM_EXPORT, WASM_MEMORY) } else { String::new() };
+              self.writer.write_all(format!("{}{}({}{} {})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_MEMORY, export, pages).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+              self.writer.write_all(format!("{}{}({} {} ({} {}) ({}{} {}))", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_GLOBAL, names::internal(&[], STACK_POINTER), WASM_MUTABLE, NTYPE_INTEGER, NTYPE_INTEGER, WASM_CONSTANT, pages * PAGE).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+            }
+            if writes && self.target == Target::WASI {
+              self.visit_writeln()?;
+            }
+            // constants, variables and routines of the program are declared before the function, variables as globals
+            let mut body = vec![];
+            for _child in &node.children[1].children {
+              match &_child.token {
+                Token::VAR => self.visit_variable_declaration(_child, true)?,
+                Token::CONST => self.visit_constant_declaration(_child)?,
+                Token::PROCEDURE | Token::FUNCTION => self.visit_routine(_child)?,
+                _ => body.push(_child.clone())
+              }
+            }
+            self.writer.write_all(format!("{}{}({} {} ({} \"{}\")", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_FUNCTION, names::mangle(&self.scopes, _string), WASM_EXPORT, export).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+            self.tab_pos += 1;
+            self.visit_temporaries(&body)?;
+            self.visit(&AST::new(Token::BLOCK, body))?;
+            self.tab_pos -= 1;
+            self.writer.write_all(format!("{}{}){}{})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize - 1)).as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+            self.tab_pos -= 1;
+          }
+        }
+      },
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
+    }
+    return Ok(());
+  }
   // visit
-  fn visit(&mut self, node: &AST) {
+  fn visit(&mut self, node: &AST) -> Result<(), Diagnostic> {
     match &node.token {
       Token::INTEGER(_int) => {
-        self.visit_number(node);
+        self.visit_number(node)?;
       },
       Token::REAL(_float) => {
-        self.visit_number(node);
+        self.visit_number(node)?;
+      },
+      Token::TRUE | Token::FALSE => {
+        self.visit_boolean(node)?;
       },
-      // Token::TRUE | Token::FALSE => {
-      //   self.visit_boolean(node);
-      // },
       Token::PLUS 
         | Token::MINUS 
         | Token::MULTIPLY 
         | Token::DIVIDE 
-        // | Token::INTEGER_DIV 
-        // | Token::INTEGER_MOD 
+        | Token::INTEGER_DIV 
+        | Token::INTEGER_MOD 
         | Token::EQUAL 
-        | Token::GREATER_THAN => {
-        self.visit_binary_operator(node);
+        | Token::NOT_EQUAL 
+        | Token::GREATER_THAN 
+        | Token::GREATER_EQUAL 
+        | Token::LESS_THAN 
+        | Token::LESS_EQUAL => {
+        self.visit_binary_operator(node)?;
       },
       Token::ASSIGN => {
-        self.visit_assign(node);
+        self.visit_assign(node)?;
       },
       Token::WHILE => {
-        self.visit_while(node);
+        self.visit_while(node)?;
       },
       Token::IF => {
-        self.visit_if(node);
+        self.visit_if(node)?;
       },
       Token::ID(_string) => {
-        self.visit_variable(node);
-      },
-      // Token::WRITELN => {
-      //   self.visit(&node.children[0]);
-      //   // write
-      //   if OUTPUT_VERBOSE { self.file.write_all(format!("{}{};; write", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize)).as_bytes()).expect(PANIC_WRITE); };
-      //   self.file.write_all(format!("{}{}({})", FORMAT_NEWLINE, FORMAT_TAB.repeat(self.tab_pos as usize), WASM_WRITE).as_bytes()).expect(PANIC_WRITE);
-      // },
-      // Token::CONST => {
-      //   self.visit_constant_declaration(node);
-      // },
-      // Token::VAR => {
-      //   self.visit_variable_declaration(node);
-      // },
+        self.visit_variable(node)?;
+      },
+      // a call as a statement
+      Token::CALL => {
+        self.visit_call(node, false)?;
+      },
+      Token::PROCEDURE | Token::FUNCTION => {
+        self.visit_routine(node)?;
+      },
+      Token::CONST => {
+        self.visit_constant_declaration(node)?;
+      },
+      Token::VAR => {
+        self.visit_variable_declaration(node, false)?;
+      },
       Token::BLOCK => {
         // block can have multiple children
         for _child in &node.children {
-          self.visit(_child);
+          self.visit(_child)?;
         }
       },
-      // Token::PROGRAM => {
-      //   // if DEBUG && DEBUG_SHOW_TREE { println!("{} {:?} {:?}", FORMAT_TAB.repeat(self.tab_pos as usize + 1), node.token, node.children[0].token); };
-      //   self.visit_program(node);
-      // },
+      Token::PROGRAM => {
+        self.visit_program(node)?;
+      },
+      Token::COMMENT(_text) => {
+        self.visit_comment(node)?;
+      },
       Token::EMPTY => {
       },
-      _ => panic!("{} : {:?}", PANIC_COMPILE, node)
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_COMPILE, node)))
     }
+    return Ok(());
+  }
+  // emit
+  pub fn emit(&mut self, tree: &AST) -> Result<(), Diagnostic> {
+    self.writer.write_all(format!(";; this file is generated").as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    // variables passed by reference need an address, so they are kept in memory, as arrays are
+    let references = Emitter::<W>::references(tree)?;
+    self.memory = !references.is_empty() || self.target == Target::WASI || Emitter::<W>::flatten(tree).into_iter().any(|node| node.token == Token::RANGE);
+    self.addressed = Emitter::<W>::addressed(tree, &references);
+    self.visit(tree)?;
+    self.writer.write_all(FORMAT_NEWLINE.as_bytes()).map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    self.writer.flush().map_err(|why| Diagnostic::new(format!("{} : {}", PANIC_WRITE, why)))?;
+    return Ok(());
+  }
+  // into_inner : W
+  pub fn into_inner(self) -> W {
+    return self.writer;
   }
   // compile
-  pub fn compile(&mut self) {
-    let tree = self.parser.parse();
-    if DEBUG && DEBUG_SHOW_TREE { println!("{:?}", tree); };
-    // END TEST
-    if DEBUG && DEBUG_SHOW_SYMBOL_TABLE { println!("{:?}", self.parser.symbol_table); };
-    if DEBUG && DEBUG_SHOW_ASSIGNMENT_TABLE { println!("{:?}", self.parser.assign_table); };
+  pub fn compile(&mut self, parser: &mut Parser) -> Result<(), Diagnostic> {
+    let tree = parser.parse()?;
     // write
-    self.file.write_all(format!(";; this file is generated").as_bytes()).expect(PANIC_WRITE);
-    self.visit(&tree);
+    self.emit(&tree)?;
+    return Ok(());
+  }
+}
+
+// glue(&str, Target) -> String
+pub fn glue(name: &str, target: Target) -> String {
+  match target {
+    // node runs the module with its WASI, which calls _start
+    Target::WASI => return format!("/* this file is generated */{}const {{ WASI }} = require('wasi');{}const wasi = new WASI({{ version: 'preview1' }});{}const wasmInstance = new WebAssembly.Instance(wasmModule, {{ {}: wasi.wasiImport }});{}wasi.start(wasmInstance);{}", FORMAT_NEWLINE, FORMAT_NEWLINE, FORMAT_NEWLINE, WASI_MODULE, FORMAT_NEWLINE, FORMAT_NEWLINE),
+    _ => return format!("/* this file is generated */{}const wasmInstance = new WebAssembly.Instance(wasmModule, {{console}});{}const {{ {} }} = wasmInstance.exports;{}{}();{}", FORMAT_NEWLINE, FORMAT_NEWLINE, name, FORMAT_NEWLINE, name, FORMAT_NEWLINE)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Lexer;
+  use crate::token::Span;
+
+  fn emit(source: &str, comments: bool) -> String {
+    let options = Options { comments: comments, trivia: comments, ..Options::default() };
+    let tree = Parser::with_options(Lexer::with_options(source.to_string(), &options), &options).unwrap().parse_statement().unwrap();
+    let mut emitter = Emitter::new(vec![], &options);
+    emitter.emit(&tree).unwrap();
+    return String::from_utf8(emitter.into_inner()).unwrap();
+  }
+
+  fn compile(source: &str) -> Result<String, Diagnostic> {
+    let options = Options { comments: false, ..Options::default() };
+    let mut parser = Parser::new(Lexer::new(source.to_string())).unwrap();
+    let mut emitter = Emitter::new(vec![], &options);
+    return emitter.compile(&mut parser).map(|_| String::from_utf8(emitter.into_inner()).unwrap());
+  }
+
+  #[test]
+  fn program() {
+    let options = Options { comments: false, ..Options::default() };
+    let mut parser = Parser::new(Lexer::new("program Loop(output); var i: integer; begin while i <= 2 do i := i + 1 end.".to_string())).unwrap();
+    let mut emitter = Emitter::new(vec![], &options);
+    emitter.compile(&mut parser).unwrap();
+    assert_eq!(
+      String::from_utf8(emitter.into_inner()).unwrap(),
+      ";; this file is generated
+      (module
+        (global $i (mut i32) (i32.const 0))
+        (func $loop (export \"loop\")
+          (block
+            (loop
+              (br_if 1
+                (global.get $i)
+                (i32.const 2)
+                (i32.gt_s)
+              )
+              (global.set $i
+                (global.get $i)
+                (i32.const 1)
+                (i32.add)
+              )
+              (br 0)
+            )
+          )
+        )
+      )
+      ".replace("\n      ", "\n")
+    );
+    let options = Options { comments: false, target: Target::WASI, ..Options::default() };
+    let mut parser = Parser::new(Lexer::new("program p; begin end.".to_string())).unwrap();
+    let mut emitter = Emitter::new(vec![], &options);
+    emitter.compile(&mut parser).unwrap();
+    assert!(String::from_utf8(emitter.into_inner()).unwrap().contains("(func $p (export \"_start\")"));
+    // on WASI writeln writes text to stdout with fd_write, from memory exported for it
+    let mut parser = Parser::new(Lexer::new("program p; begin writeln(1) end.".to_string())).unwrap();
+    let mut emitter = Emitter::new(vec![], &options);
+    emitter.compile(&mut parser).unwrap();
+    let wat = String::from_utf8(emitter.into_inner()).unwrap();
+    assert!(wat.contains("(import \"wasi_snapshot_preview1\" \"fd_write\" (func $!fd_write (param i32 i32 i32 i32) (result i32)))\n  (memory (export \"memory\") 1)"));
+    assert!(wat.contains("(func $!writeln (param $value f64)"));
+    assert!(wat.contains("(call $!writeln\n      (f64.const 1)\n    )"));
+    assert!(!wat.contains(IMPORT_MODULE));
+    // the glue runs the module in the browser, or with the WASI of node
+    assert!(glue("p", Target::BROWSER).contains("new WebAssembly.Instance(wasmModule, {console});\nconst { p } = wasmInstance.exports;\np();"));
+    assert!(glue("p", Target::WASI).contains("new WebAssembly.Instance(wasmModule, { wasi_snapshot_preview1: wasi.wasiImport });\nwasi.start(wasmInstance);"));
+  }
+
+  #[test]
+  fn assign() {
+    assert_eq!(
+      emit("test := 42;", false),
+      ";; this file is generated
+      (local.set $test
+        (f64.const 42)
+      )
+      ".replace("\n      ", "\n")
+    );
+  }
+
+  #[test]
+  fn comment() {
+    assert_eq!(
+      emit("{ the answer,\n  of course } test := 42; // done", true),
+      ";; this file is generated
+      ;; { the answer,
+      ;; of course }
+      ;; assignment statement
+      (local.set $test
+        ;; number
+        (f64.const 42)
+      )
+      ;; // done
+      ".replace("\n      ", "\n")
+    );
+  }
+
+  #[test]
+  fn names() {
+    assert_eq!(
+      emit("Fn := fn + smörgås;", false),
+      ";; this file is generated
+      (local.set $fn
+        (local.get $fn)
+        (local.get $sm.0000f6rg.0000e5s)
+        (f64.add)
+      )
+      ".replace("\n      ", "\n")
+    );
+  }
+
+  #[test]
+  fn types() {
+    let wat = compile("program p; var i: integer; r: real; f: boolean; begin r := i; f := r < 1; i := -i end.").unwrap();
+    assert!(wat.contains("(global $r (mut f64) (f64.const 0))"));
+    assert!(wat.contains("(global.get $i)\n      (f64.convert_i32_s)"));
+    assert!(wat.contains("(f64.const 1)\n      (f64.lt)"));
+    assert!(wat.contains("(i32.const -1)\n      (i32.mul)"));
+    // reals are not rounded into integers, and booleans are not numbers
+    assert!(compile("program p; var i: integer; begin i := 1.5 end.").unwrap_err().message.starts_with(PANIC_TYPE_MISMATCH));
+    assert!(compile("program p; var f: boolean; begin f := 1 end.").unwrap_err().message.starts_with(PANIC_TYPE_MISMATCH));
+    assert!(compile("program p; var i: integer; begin i := i div 2.0 end.").unwrap_err().message.starts_with(PANIC_TYPE_MISMATCH));
+    assert!(compile("program p; begin x := 1 end.").unwrap_err().message.starts_with(PANIC_VAR_NOT_DECLARAED));
+    // errors are at the node in the source
+    assert_eq!(compile("program p; var i: integer; begin i := 1.5 end.").unwrap_err().span, Some(Span::new(38, 41, 1, 39)));
+    assert_eq!(compile("program p;\nbegin x := 1 end.").unwrap_err().to_string(), format!("2:7 : {} : x", PANIC_VAR_NOT_DECLARAED));
+    // literals beyond 32 bits are reals
+    assert!(compile("program p; var r: real; begin r := 5000000000 end.").unwrap().contains("(f64.const 5000000000.0)"));
+    assert!(compile("program p; var i: integer; begin i := 5000000000 end.").unwrap_err().message.starts_with(PANIC_TYPE_MISMATCH));
+    assert!(compile("program p; const Min = -2147483648; var i: integer; begin i := Min; i := $FFFFFFFF end.").unwrap().contains("(i32.const -2147483648)"));
+    let mut emitter = Emitter::new(vec![], &Options::default());
+    assert!(emitter.visit_number(&AST::new(Token::INTEGER(1 << 40), vec![])).unwrap_err().message.starts_with(PANIC_INTEGER_RANGE));
+  }
+
+  #[test]
+  fn constants() {
+    let options = Options { comments: true, ..Options::default() };
+    let mut parser = Parser::new(Lexer::new("program p; const Size = 2; var v: array[1..Size] of real; begin v[Size] := Size end.".to_string())).unwrap();
+    let mut emitter = Emitter::new(vec![], &options);
+    emitter.compile(&mut parser).unwrap();
+    let wat = String::from_utf8(emitter.into_inner()).unwrap();
+    assert!(wat.contains("(memory 1)\n  (global $!sp (mut i32) (i32.const 65536))\n  ;; constant size = 2\n  ;; variable declaration\n  (func $p"));
+    assert!(wat.contains("(f64.store (i32.const 8)\n      ;; number\n      (f64.const 2)"));
+    let mut parser = Parser::new(Lexer::new("program p; var v: array[1..2] of real; begin v[3] := 0 end.".to_string())).unwrap();
+    assert!(Emitter::new(vec![], &options).compile(&mut parser).unwrap_err().message.starts_with(PANIC_VAR_NOT_DECLARAED));
+  }
+
+  #[test]
+  fn indexes() {
+    // an index known only when running is checked against the bounds, the element is at first + (index - low) * size
+    let wat = compile("program p; var v: array[1..3] of real; i: integer; begin v[i] := v[i + 1] end.").unwrap();
+    assert!(wat.contains("(func $p (export \"p\")\n    (local $!index i32)\n    (local $!i32 i32)\n    (local $!f64 f64)"));
+    assert!(wat.contains("(local.set $!f64\n      (local.set $!index\n        (global.get $i)\n        (i32.const 1)\n        (i32.add)\n      )"));
+    assert!(wat.contains("(if (i32.gt_u (i32.sub (local.get $!index) (i32.const 1)) (i32.const 2)) (then (unreachable)))\n      (f64.load (i32.add (i32.const 0) (i32.mul (i32.sub (local.get $!index) (i32.const 1)) (i32.const 8))))\n    )"));
+    assert!(wat.contains("(f64.store (i32.add (i32.const 0) (i32.mul (i32.sub (local.get $!index) (i32.const 1)) (i32.const 8))) (local.get $!f64))"));
+    // locals in a procedure are found before globals of the same name, in the frame
+    let wat = compile("program p; var v: array[1..3] of real; procedure q(i: integer); var v: array[0..1] of integer; begin v[i] := 1 end; begin end.").unwrap();
+    assert!(wat.contains("(local $q/!index i32)"));
+    assert!(wat.contains("(global.set $!sp (i32.sub (global.get $!sp) (i32.const 16)))"));
+    assert!(wat.contains("(i32.store (i32.add (i32.add (local.get $q/!frame) (i32.const 0)) (i32.mul (i32.sub (local.get $q/!index) (i32.const 0)) (i32.const 8))) (local.get $q/!i32))"));
+    // an element passed by reference has its address
+    let wat = compile("program p; var v: array[1..3] of real; i: integer; procedure q(var r: real); begin end; begin q(v[i]); q(v[3]) end.").unwrap();
+    assert!(wat.contains("(then (unreachable)))\n      (i32.add (i32.const 0) (i32.mul (i32.sub (local.get $!index) (i32.const 1)) (i32.const 8)))\n    )"));
+    assert!(wat.contains("(call $q\n      (i32.const 16)\n    )"));
+    // the index is an integer, and an array is not a variable
+    assert!(compile("program p; var v: array[1..3] of real; r: real; begin v[r] := 0 end.").unwrap_err().message.starts_with(PANIC_TYPE_MISMATCH));
+    assert!(compile("program p; var v: array[1..3] of real; begin v := 0 end.").unwrap_err().message.starts_with(PANIC_TYPE_MISMATCH));
+    assert!(compile("program p; var v: array[1..3] of integer; procedure q(var r: real); begin end; begin q(v[1]) end.").unwrap_err().message.starts_with(PANIC_TYPE_MISMATCH));
+    // memory grows for large arrays, and ranges which do not fit are an error
+    assert!(compile("program p; var v: array[1..100000] of integer; begin end.").unwrap().contains("(memory 13)\n  (global $!sp (mut i32) (i32.const 851968))"));
+    assert_eq!(compile("program p; var v: array[1..2000000000] of integer; begin end.").unwrap_err().to_string(), format!("1:16 : {} : v", PANIC_MEMORY));
+    assert_eq!(compile("program p; procedure q; var v: array[1..5000] of integer; begin end; begin end.").unwrap_err().to_string(), format!("1:29 : {} : v", PANIC_MEMORY));
+  }
+
+  #[test]
+  fn routines() {
+    let wat = compile("program p; var n: integer; function Fact(n: integer): integer; begin if n <= 1 then Fact := 1 else Result := n * Fact(n - 1) end; procedure Show(x: real); begin writeln(x) end; begin n := Fact(5); Show(n); Fact(1) end.").unwrap();
+    assert!(wat.contains("(import \"console\" \"log\" (func $log (param f64)))"));
+    assert!(wat.contains("(func $fact (param $fact/n i32) (result i32)\n    (local $fact/!result i32)"));
+    assert!(wat.contains("(local.set $fact/!result\n          (local.get $fact/n)\n          (call $fact\n"));
+    assert!(wat.contains("(func $show (param $show/x f64)\n    (call $log\n      (local.get $show/x)\n    )\n  )"));
+    assert!(wat.contains("(call $show\n      (global.get $n)\n      (f64.convert_i32_s)\n    )"));
+    assert!(wat.contains("(call $fact\n      (i32.const 1)\n    )\n    (drop)"));
+    // routines declared inside another are functions of their own, named after it
+    let wat = compile("program p; procedure Outer; procedure Inner; begin end; begin Inner end; begin Outer end.").unwrap();
+    assert!(wat.find("(func $outer/inner").unwrap() < wat.find("(func $outer\n").unwrap());
+    assert!(!wat.contains(WASM_IMPORT));
+    assert!(compile("program p; procedure Outer; procedure Inner; begin end; begin end; begin Inner end.").unwrap_err().message.starts_with(PANIC_ROUTINE_NOT_DECLARED));
+    assert!(compile("program p; procedure q(x: integer); begin end; begin q(1, 2) end.").unwrap_err().message.starts_with(PANIC_ARGUMENTS));
+    assert!(compile("program p; var x: integer; procedure q; begin end; begin x := q end.").unwrap_err().message.starts_with(PANIC_TYPE_MISMATCH));
+    assert!(compile("program p; procedure q; var x: integer; begin end; begin x := 1 end.").unwrap_err().message.starts_with(PANIC_VAR_NOT_DECLARAED));
+    // parameters and local variables hide a constant of the same name
+    let wat = compile("program p; const N = 10; function F(n: integer): integer; begin F := n end; procedure q; var n: integer; begin n := 1 end; begin writeln(F(N)) end.").unwrap();
+    assert!(wat.contains("(local.set $f/!result\n      (local.get $f/n)\n    )"));
+    assert!(wat.contains("(local.set $q/n\n      (i32.const 1)\n    )"));
+    assert!(wat.contains("(call $f\n        (i32.const 10)\n      )"));
+    // the name of a function in an expression calls it again, while Result and a variable called result are different locals
+    let wat = compile("program p; function G: integer; begin Result := 1; if G > 0 then G := 2 end; function F(result: integer): integer; begin F := result end; begin writeln(F(G)) end.").unwrap();
+    assert!(wat.contains("(local.set $g/!result\n      (i32.const 1)\n    )"));
+    assert!(wat.contains("(result i32)\n        (call $g\n        )\n        (i32.const 0)"));
+    assert!(wat.contains("(func $f (param $f/result i32) (result i32)\n    (local $f/!result i32)\n    (local.set $f/!result\n      (local.get $f/result)\n    )"));
+    assert!(compile("program p; function F(n: integer): integer; begin F := F end; begin end.").unwrap_err().message.starts_with(PANIC_ARGUMENTS));
+    // routines declared inside another cannot use its parameters and variables, even with a global of the same name
+    assert!(compile("program p; var x: integer; procedure Outer; var x: integer; procedure Inner; begin x := 1 end; begin end; begin end.").unwrap_err().message.starts_with(PANIC_NONLOCAL));
+    assert!(compile("program p; procedure Outer(n: integer); procedure Inner; begin writeln(n) end; begin end; begin end.").unwrap_err().message.starts_with(PANIC_NONLOCAL));
+    assert!(compile("program p; procedure Outer; var v: array[1..2] of integer; procedure Inner; begin v[1] := 0 end; begin end; begin end.").unwrap_err().message.starts_with(PANIC_NONLOCAL));
+    assert!(compile("program p; procedure Outer; var x: integer; procedure Inner; var x: integer; begin x := 1 end; begin end; begin end.").is_ok());
+  }
+
+  #[test]
+  fn references() {
+    let swap = "procedure Swap(var a, b: integer); var t: integer; begin t := a; a := b; b := t end;";
+    let wat = compile(&format!("program p; var x, y: integer; r: real; {} function Twice(const v: real): real; begin Twice := 2 * v end; procedure Keep(n: integer); begin Swap(n, x) end; begin Swap(x, y); r := Twice(r + 1) end.", swap)).unwrap();
+    assert!(wat.contains("(memory 1)\n  (global $!sp (mut i32) (i32.const 65536))\n  (global $r (mut f64) (f64.const 0))\n  (func $swap (param $swap/a i32) (param $swap/b i32)"));
+    assert!(wat.contains("(i32.store (local.get $swap/a)\n      (i32.load (local.get $swap/b))\n    )"));
+    // variables passed by reference are in memory, globals at an address and locals in the frame of the function
+    assert!(wat.contains("(call $swap\n      (i32.const 0)\n      (i32.const 8)\n    )"));
+    // the stack would cross into the 16 bytes of globals when the stack pointer is below them and the bytes taken
+    assert!(wat.contains("(local $keep/!frame i32)\n    (if (i32.lt_u (global.get $!sp) (i32.const 24)) (then (unreachable)))\n    (global.set $!sp (i32.sub (global.get $!sp) (i32.const 8)))\n    (local.set $keep/!frame (global.get $!sp))"));
+    assert!(wat.contains("(call $twice\n        (if (i32.lt_u (global.get $!sp) (i32.const 24)) (then (unreachable)))\n        (global.set $!sp (i32.sub (global.get $!sp) (i32.const 8)))"));
+    assert!(wat.contains("(i32.store (i32.add (local.get $keep/!frame) (i32.const 0)) (local.get $keep/n))"));
+    assert!(wat.contains("(global.set $!sp (i32.add (local.get $keep/!frame) (i32.const 8)))\n  )"));
+    // other values for const parameters are on the stack during the call
+    assert!(wat.contains("(f64.store (global.get $!sp)\n"));
+    assert!(wat.contains("(global.set $!sp (i32.add (global.get $!sp) (i32.const 8)))"));
+    assert!(!compile("program p; var x: integer; begin x := 1 end.").unwrap().contains(WASM_MEMORY));
+    let error = |source: &str| compile(&format!("program p; var x: integer; r: real; {} begin {} end.", swap, source)).unwrap_err().message;
+    assert!(error("Swap(x, 1)").starts_with(PANIC_VAR_ARGUMENT));
+    assert!(error("Swap(x, r)").starts_with(PANIC_TYPE_MISMATCH));
+    assert!(compile("program p; procedure Keep(const c: integer); begin c := 1 end; begin end.").unwrap_err().message.starts_with(PANIC_CONSTANT_ASSIGNMENT));
+    assert!(compile(&format!("program p; {} procedure Keep(const c: integer); begin Swap(c, c) end; begin end.", swap)).unwrap_err().message.starts_with(PANIC_CONSTANT_ASSIGNMENT));
+  }
+
+  #[test]
+  fn binary_operator() {
+    assert_eq!(
+      emit("test := 2 * (4 - 1);", true),
+      ";; this file is generated
+      ;; assignment statement
+      (local.set $test
+        ;; number
+        (i32.const 2)
+        ;; number
+        (i32.const 4)
+        ;; number
+        (i32.const 1)
+        ;; binary operator
+        (i32.sub)
+        ;; binary operator
+        (i32.mul)
+        (f64.convert_i32_s)
+      )
+      ".replace("\n      ", "\n")
+    );
   }
 }
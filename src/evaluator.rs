@@ -1,10 +1,14 @@
+use std::convert::TryFrom;
+
 use crate::consts::*;
 use crate::token::{ Type, Token };
 use crate::ast::AST;
+use crate::diagnostic::Diagnostic;
+use crate::names;
 
 pub struct Evaluator {
   pub symbol_table: Vec<(Token, Type)>,
-  assign_table: Vec<(Token, AST)>,
+  pub assign_table: Vec<(Token, AST)>,
   tokens: Vec<Token>,
 }
 
@@ -19,74 +23,259 @@ impl Evaluator {
     return evaluator;
   }
   // eval_number : f64
-  fn eval_number(&mut self, node: &AST) -> f64 {
+  fn eval_number(&mut self, node: &AST) -> Result<f64, Diagnostic> {
     match node.token {
       Token::INTEGER(_int) => {
         self.tokens.push(node.token.clone());
-          return _int as f64;
+          return Ok(_int as f64);
         },
         Token::REAL(_float) => {
           self.tokens.push(node.token.clone());
-          return _float;
+          return Ok(_float);
         },
-        _ => panic!("{} : {:?}", PANIC_EVAL, node)
+        _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_EVAL, node)))
       }
   }
-  // eval_binary_operator : f64
-  fn eval_binary_operator(&mut self, node: &AST) -> f64 {
-    let left_value = self.evaluate(&node.children[0]);
-    let right_value = self.evaluate(&node.children[1]);
+  // eval_unary_operator : f64
+  fn eval_unary_operator(&mut self, node: &AST) -> Result<f64, Diagnostic> {
+    let value = self.evaluate(&node.children[0])?;
     match node.token {
       Token::PLUS => {
         self.tokens.push(node.token.clone());
-        return left_value + right_value;
+        return Ok(value);
       },
       Token::MINUS => {
         self.tokens.push(node.token.clone());
-        return left_value - right_value;
+        return Ok(-value);
+      },
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_EVAL, node)))
+    }
+  }
+  // eval_binary_operator : f64
+  fn eval_binary_operator(&mut self, node: &AST) -> Result<f64, Diagnostic> {
+    if node.children.len() as i32 != 2 {
+      return self.eval_unary_operator(node);
+    }
+    let left_value = self.evaluate(&node.children[0])?;
+    let right_value = self.evaluate(&node.children[1])?;
+    self.tokens.push(node.token.clone());
+    match node.token {
+      Token::INTEGER_DIV | Token::INTEGER_MOD if right_value.trunc() == 0.0 => {
+        return Err(Diagnostic::new(format!("{} : {:?}", PANIC_DIVISION_BY_ZERO, node.token)));
+      },
+      Token::PLUS => {
+        return Ok(left_value + right_value);
+      },
+      Token::MINUS => {
+        return Ok(left_value - right_value);
       },
       Token::MULTIPLY => {
-        self.tokens.push(node.token.clone());
-        return left_value * right_value;
+        return Ok(left_value * right_value);
       },
-      // Token::DIVIDE | Token::INTEGER_DIV => {
-      //   self.tokens.push(node.token.clone());
-      //   return left_value / right_value;
-      // },
       Token::DIVIDE => {
-        self.tokens.push(node.token.clone());
-        return left_value / right_value;
+        return Ok(left_value / right_value);
       }
-      // Token::INTEGER_MOD => {
-      //   self.tokens.push(node.token.clone());
-      //   return left_value % right_value;
-      // },
-      _ => panic!("{} : {:?}", PANIC_EVAL, node)
+      Token::INTEGER_DIV => {
+        return Ok((left_value.trunc() / right_value.trunc()).trunc());
+      },
+      Token::INTEGER_MOD => {
+        return Ok(left_value.trunc() % right_value.trunc());
+      },
+      // comparisons are 1 for true and 0 for false
+      Token::EQUAL => {
+        return Ok((left_value == right_value) as i32 as f64);
+      },
+      Token::NOT_EQUAL => {
+        return Ok((left_value != right_value) as i32 as f64);
+      },
+      Token::GREATER_THAN => {
+        return Ok((left_value > right_value) as i32 as f64);
+      },
+      Token::GREATER_EQUAL => {
+        return Ok((left_value >= right_value) as i32 as f64);
+      },
+      Token::LESS_THAN => {
+        return Ok((left_value < right_value) as i32 as f64);
+      },
+      Token::LESS_EQUAL => {
+        return Ok((left_value <= right_value) as i32 as f64);
+      },
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_EVAL, node)))
+    }
+  }
+  // type_of(&AST) -> Result<Type, Diagnostic>
+  fn type_of(&self, node: &AST) -> Result<Type, Diagnostic> {
+    match &node.token {
+      Token::INTEGER(_) => return Ok(Type::INTEGER),
+      Token::REAL(_) => return Ok(Type::REAL),
+      Token::TRUE | Token::FALSE => return Ok(Type::BOOLEAN),
+      Token::ID(_string) => {
+        for symbol in &self.assign_table {
+          if names::same(&node.token, &symbol.0) {
+            return self.type_of(&symbol.1);
+          }
+        }
+        return Err(Diagnostic::new(format!("{} : {} : {:?}", PANIC_EVAL, PANIC_VAR_NOT_DECLARAED, node)))
+      },
+      Token::DIVIDE => return Ok(Type::REAL),
+      Token::INTEGER_DIV | Token::INTEGER_MOD => return Ok(Type::INTEGER),
+      Token::EQUAL
+        | Token::NOT_EQUAL
+        | Token::GREATER_THAN
+        | Token::GREATER_EQUAL
+        | Token::LESS_THAN
+        | Token::LESS_EQUAL => return Ok(Type::BOOLEAN),
+      Token::PLUS | Token::MINUS | Token::MULTIPLY => {
+        // real when any operand is real
+        for _child in &node.children {
+          if self.type_of(_child)? == Type::REAL {
+            return Ok(Type::REAL);
+          }
+        }
+        return Ok(Type::INTEGER);
+      },
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_EVAL, node)))
+    }
+  }
+  // integer(&AST) -> Result<i64, Diagnostic>
+  fn integer(&self, node: &AST) -> Result<i64, Diagnostic> {
+    // integer expressions are computed exactly, so overflow and division by zero are found
+    let overflow = || Diagnostic::new(format!("{} : {:?}", PANIC_OVERFLOW, node.token));
+    match (&node.token, node.children.len()) {
+      (Token::INTEGER(_int), _) => return Ok(*_int),
+      (Token::ID(_string), _) => {
+        for symbol in &self.assign_table {
+          if names::same(&node.token, &symbol.0) {
+            return self.integer(&symbol.1);
+          }
+        }
+        return Err(Diagnostic::new(format!("{} : {} : {:?}", PANIC_EVAL, PANIC_VAR_NOT_DECLARAED, node)))
+      },
+      (Token::PLUS, 1) => return self.integer(&node.children[0]),
+      (Token::MINUS, 1) => return self.integer(&node.children[0])?.checked_neg().ok_or_else(overflow),
+      (_, 2) => {},
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_EVAL, node)))
+    }
+    let left_value = self.integer(&node.children[0])?;
+    let right_value = self.integer(&node.children[1])?;
+    match node.token {
+      Token::PLUS => return left_value.checked_add(right_value).ok_or_else(overflow),
+      Token::MINUS => return left_value.checked_sub(right_value).ok_or_else(overflow),
+      Token::MULTIPLY => return left_value.checked_mul(right_value).ok_or_else(overflow),
+      Token::INTEGER_DIV | Token::INTEGER_MOD if right_value == 0 => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_DIVISION_BY_ZERO, node.token))),
+      Token::INTEGER_DIV => return left_value.checked_div(right_value).ok_or_else(overflow),
+      Token::INTEGER_MOD => return left_value.checked_rem(right_value).ok_or_else(overflow),
+      _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_EVAL, node)))
+    }
+  }
+  // constant(&AST) -> Result<AST, Diagnostic>
+  pub fn constant(&mut self, node: &AST) -> Result<AST, Diagnostic> {
+    // the value of a constant expression as a literal of its type, integers are 32-bit
+    match self.type_of(node)? {
+      Type::INTEGER => {
+        let value = self.integer(node)?;
+        if i32::try_from(value).is_err() {
+          return Err(Diagnostic::new(format!("{} : {}", PANIC_OVERFLOW, value)));
+        }
+        return Ok(AST::new(Token::INTEGER(value), vec![]));
+      },
+      Type::REAL => return Ok(AST::new(Token::REAL(self.evaluate(node)?), vec![])),
+      Type::BOOLEAN if self.evaluate(node)? != 0.0 => return Ok(AST::new(Token::TRUE, vec![])),
+      Type::BOOLEAN => return Ok(AST::new(Token::FALSE, vec![])),
     }
   }
   // evaluate : f64
-  pub fn evaluate(&mut self, node: &AST) -> f64 {
+  pub fn evaluate(&mut self, node: &AST) -> Result<f64, Diagnostic> {
     match &node.token {
       Token::INTEGER(_) | Token::REAL(_) => {
         return self.eval_number(node);
       },
-      Token::PLUS | Token::MINUS | Token::MULTIPLY | Token::DIVIDE => {
+      // booleans are 1 for true and 0 for false, as comparisons
+      Token::TRUE => {
+        return Ok(1.0);
+      },
+      Token::FALSE => {
+        return Ok(0.0);
+      },
+      Token::PLUS
+        | Token::MINUS
+        | Token::MULTIPLY
+        | Token::DIVIDE
+        | Token::INTEGER_DIV
+        | Token::INTEGER_MOD
+        | Token::EQUAL
+        | Token::NOT_EQUAL
+        | Token::GREATER_THAN
+        | Token::GREATER_EQUAL
+        | Token::LESS_THAN
+        | Token::LESS_EQUAL => {
         return self.eval_binary_operator(node);
       },
       Token::ID(_string) => {
         if self.assign_table.len() as i32 > 0 {
           for symbol in &self.assign_table {
-            if node.token == symbol.0 {
+            if names::same(&node.token, &symbol.0) {
               let node = symbol.1.clone();
-              let value = self.evaluate(&node);
-                return value;
+              let value = self.evaluate(&node)?;
+                return Ok(value);
               }
             }
           }
-          panic!("{} : {:?}", PANIC_EVAL, node)
+          return Err(Diagnostic::new(format!("{} : {} : {:?}", PANIC_EVAL, PANIC_VAR_NOT_DECLARAED, node)))
         }
-        _ => panic!("{} : {:?}", PANIC_EVAL, node)
+        _ => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_EVAL, node)))
     }
   }
 }
-// ----------------------------------------------------
\ No newline at end of file
+// ----------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+
+  fn eval(source: &str, assign_table: Vec<(Token, AST)>) -> Result<f64, Diagnostic> {
+    let tree = Parser::new(Lexer::new(source.to_string())).unwrap().parse_expression().unwrap();
+    return Evaluator::new(vec![], assign_table).evaluate(&tree);
+  }
+
+  #[test]
+  fn evaluate() {
+    assert_eq!(eval("(((6 * 24) - 6) + (4 * 32 * 3)) / (4 * 2 + 4) - 1.5", vec![]), Ok(42.0));
+    assert_eq!(eval("-(2 + 3)", vec![]), Ok(-5.0));
+    assert_eq!(eval("2 < 3", vec![]), Ok(1.0));
+    assert_eq!(eval("ODD(7)", vec![]), Ok(1.0));
+    assert_eq!(eval("ODD(-3)", vec![]), Ok(1.0));
+    assert_eq!(eval("7 div 2 + 7 mod 2", vec![]), Ok(4.0));
+    assert_eq!(eval("true", vec![]), Ok(1.0));
+    assert_eq!(eval("x * 2", vec![(Token::ID("x".to_string()), AST::new(Token::INTEGER(21), vec![]))]), Ok(42.0));
+    assert_eq!(eval("X * 2", vec![(Token::ID("x".to_string()), AST::new(Token::INTEGER(21), vec![]))]), Ok(42.0));
+    assert!(eval("y", vec![]).is_err());
+  }
+
+  #[test]
+  fn constant() {
+    let constants = vec![(Token::ID("n".to_string()), AST::new(Token::INTEGER(10), vec![]))];
+    let constant = |source: &str| {
+      let tree = Parser::new(Lexer::new(source.to_string())).unwrap().parse_expression().unwrap();
+      return Evaluator::new(vec![], constants.clone()).constant(&tree).unwrap().token;
+    };
+    assert_eq!(constant("N div 2"), Token::INTEGER(5));
+    assert_eq!(constant("N / 4"), Token::REAL(2.5));
+    assert_eq!(constant("-N * 1.5"), Token::REAL(-15.0));
+    assert_eq!(constant("N > 5"), Token::TRUE);
+    assert_eq!(constant("-2147483648"), Token::INTEGER(-2147483648));
+    assert_eq!(constant("$FFFFFFFF"), Token::INTEGER(-1));
+    // integers are computed exactly, overflow and division by zero are errors
+    let error = |source: &str| {
+      let tree = Parser::new(Lexer::new(source.to_string())).unwrap().parse_expression().unwrap();
+      return Evaluator::new(vec![], constants.clone()).constant(&tree).unwrap_err().message;
+    };
+    assert!(error("1 div 0").starts_with(PANIC_DIVISION_BY_ZERO));
+    assert!(error("7 mod (N - 10)").starts_with(PANIC_DIVISION_BY_ZERO));
+    assert!(error("1.5 * (7 mod 0)").starts_with(PANIC_DIVISION_BY_ZERO));
+    assert!(error("2147483647 + 1").starts_with(PANIC_OVERFLOW));
+    assert!(error("65536 * 65536 * 65536 * 65536 div 2").starts_with(PANIC_OVERFLOW));
+  }
+}
@@ -0,0 +1,423 @@
+use crate::consts::*;
+use crate::token::{ Type, Token };
+use crate::ast::AST;
+use crate::diagnostic::Diagnostic;
+use crate::names;
+use crate::evaluator::Evaluator;
+
+// maximum number of loop iterations and calls in one run, so an endless loop does not hang the repl
+const MAX_STEPS: usize = 1_000_000;
+// maximum number of calls in progress, so endless recursion does not overflow the stack of the repl
+const MAX_DEPTH: usize = 64;
+const WRITELN                       : &str = "writeln";
+// variable holding the result of a function, assigned by the name of the function or Result
+const RESULT                        : &str = "result";
+
+pub struct Interpreter {
+  pub evaluator: Evaluator,
+  // constants declared so far, given to the parser of each line
+  pub constants: Vec<(Token, AST)>,
+  // procedure and function declarations
+  routines: Vec<AST>,
+  // text written by writeln
+  pub output: String,
+  steps: usize,
+  depth: usize,
+  // name of the function being run, and the parameters and variables of the routine being run
+  function: Option<Token>,
+  locals: Vec<Token>,
+}
+
+impl Interpreter {
+  // new() -> Interpreter
+  pub fn new() -> Interpreter {
+    let interpreter = Interpreter {
+      evaluator: Evaluator::new(vec![], vec![]),
+      constants: vec![],
+      routines: vec![],
+      output: String::new(),
+      steps: 0,
+      depth: 0,
+      function: None,
+      locals: vec![],
+    };
+    return interpreter;
+  }
+  // variables() -> Vec<(String, f64)>
+  pub fn variables(&mut self) -> Vec<(String, f64)> {
+    let mut variables = vec![];
+    for (_token, _node) in self.evaluator.assign_table.clone() {
+      if let (Token::ID(_string), Ok(_value)) = (&_token, self.evaluator.evaluate(&_node)) {
+        variables.push((_string.clone(), _value));
+      }
+    }
+    return variables;
+  }
+  // set(Token, AST)
+  fn set(&mut self, token: Token, node: AST) {
+    for symbol in &mut self.evaluator.assign_table {
+      if names::same(&symbol.0, &token) {
+        symbol.1 = node;
+        return;
+      }
+    }
+    self.evaluator.assign_table.push((token, node));
+  }
+  // assign(Token, f64)
+  fn assign(&mut self, token: Token, value: f64) {
+    // variables hold the value they were assigned, not the expression, integers are i32 so reals are truncated and the value wraps around
+    let integer = self.evaluator.assign_table.iter().any(|symbol| names::same(&symbol.0, &token) && matches!(symbol.1.token, Token::INTEGER(_)));
+    let node = match integer {
+      true => AST::new(Token::INTEGER(value as i64 as i32 as i64), vec![]),
+      false => AST::new(Token::REAL(value), vec![])
+    };
+    self.set(token, node);
+  }
+  // define(Token, &Type, f64)
+  fn define(&mut self, token: Token, _type: &Type, value: f64) {
+    // a variable of that type, hiding any other of the same name, booleans are integers as in the emitter
+    let zero = match _type {
+      Type::REAL => Token::REAL(0.0),
+      Type::INTEGER | Type::BOOLEAN => Token::INTEGER(0)
+    };
+    self.set(token.clone(), AST::new(zero, vec![]));
+    self.assign(token, value);
+  }
+  // result() -> Token
+  fn result() -> Token {
+    // the result is named by the interpreter, so a variable called result is another one
+    return Token::ID(names::internal(&[], RESULT));
+  }
+  // variable(&AST, bool) -> Token
+  fn variable(&self, node: &AST, assigned: bool) -> Token {
+    // inside a function Result is its result, as is its own name when assigned to, unless a parameter or variable has that name
+    match (&node.token, &self.function) {
+      (Token::ID(_string), Some(_function)) if !self.locals.iter().any(|local| names::same(local, &node.token)) && (names::canonical(_string) == RESULT || (assigned && names::same(_function, &node.token))) => return Interpreter::result(),
+      _ => return node.token.clone()
+    }
+  }
+  // lookup(Token) -> Option<f64>
+  fn lookup(&mut self, token: &Token) -> Option<f64> {
+    return self.evaluator.evaluate(&AST::new(token.clone(), vec![])).ok();
+  }
+  // routine(Token) -> Option<AST>
+  fn routine(&self, token: &Token) -> Option<AST> {
+    return self.routines.iter().find(|routine| names::same(&routine.children[0].token, token)).cloned();
+  }
+  // callable(Token) -> bool
+  pub fn callable(&self, token: &Token) -> bool {
+    return matches!(token, Token::ID(_string) if names::canonical(_string) == WRITELN) || self.routine(token).is_some();
+  }
+  // declare(AST)
+  fn declare(&mut self, node: &AST) -> Result<(), Diagnostic> {
+    match &node.token {
+      // variables start at zero, each element of an array would need its own value
+      Token::VAR => {
+        for _declaration in &node.children {
+          if let Token::TYPE_SPEC(_type) = &_declaration.token {
+            for _variable in &_declaration.children {
+              if !_variable.children.is_empty() {
+                return Err(Diagnostic::new(format!("{} : {:?}", PANIC_EVAL, _variable)));
+              }
+              self.define(_variable.token.clone(), _type, 0.0);
+            }
+          }
+        }
+      },
+      // each value has its constant as last child
+      Token::CONST => {
+        for _declaration in &node.children {
+          if let Some(_constant) = _declaration.children.last() {
+            let value = AST::new(_declaration.token.clone(), vec![]);
+            self.constants.retain(|constant| !names::same(&constant.0, &_constant.token));
+            self.constants.push((_constant.token.clone(), value));
+          }
+        }
+      },
+      Token::PROCEDURE | Token::FUNCTION => {
+        self.routines.retain(|routine| !names::same(&routine.children[0].token, &node.children[0].token));
+        self.routines.push(node.clone());
+      },
+      _ => {}
+    }
+    return Ok(());
+  }
+  // call(AST) -> Option<f64>
+  fn call(&mut self, node: &AST) -> Result<Option<f64>, Diagnostic> {
+    let name = &node.children[0].token;
+    let arguments = &node.children[1..];
+    // WRITELN writes each argument on its own line
+    if matches!(name, Token::ID(_string) if names::canonical(_string) == WRITELN) {
+      for _argument in arguments {
+        let value = self.evaluate(_argument)?;
+        self.output.push_str(&format!("{}\n", value));
+      }
+      return Ok(None);
+    }
+    let routine = match self.routine(name) {
+      Some(_routine) => _routine,
+      None => return Err(Diagnostic::new(format!("{:?} : {}", name, PANIC_ROUTINE_NOT_DECLARED)))
+    };
+    self.steps += 1;
+    if self.steps > MAX_STEPS {
+      return Err(Diagnostic::new(format!("{} : {:?}", PANIC_STEPS, name)));
+    }
+    if self.depth >= MAX_DEPTH {
+      return Err(Diagnostic::new(format!("{} : {:?}", PANIC_DEPTH, name)));
+    }
+    // parameters with their type and whether they are passed by reference, groups have the type as token
+    let mut parameters = vec![];
+    for _group in &routine.children[1].children {
+      let (group, reference) = match &_group.token {
+        Token::VAR => (&_group.children[0], true),
+        Token::CONST => (&_group.children[0], false),
+        _ => (_group, false)
+      };
+      let _type = match &group.token {
+        Token::TYPE_SPEC(_type) => _type.clone(),
+        _ => Type::REAL
+      };
+      parameters.extend(group.children.iter().map(|parameter| (parameter.token.clone(), _type.clone(), reference)));
+    }
+    if arguments.len() != parameters.len() {
+      return Err(Diagnostic::new(format!("{:?} : {}", name, PANIC_ARGUMENTS)));
+    }
+    // a variable passed by reference is read and written back by the name it has here
+    let mut values = vec![];
+    let mut targets = vec![];
+    for ((_, _, _reference), _argument) in parameters.iter().zip(arguments) {
+      if *_reference && !matches!(_argument.token, Token::ID(_)) {
+        return Err(Diagnostic::new(format!("{:?} : {}", _argument.token, PANIC_VAR_ARGUMENT)));
+      }
+      let target = if *_reference { Some(self.variable(_argument, true)) } else { None };
+      match &target {
+        Some(_token) => values.push(self.evaluate(&AST::new(_token.clone(), vec![]))?),
+        None => values.push(self.evaluate(_argument)?)
+      }
+      targets.push(target);
+    }
+    // parameters, locals and the result hide variables with the same name until the call returns
+    let saved = self.evaluator.assign_table.clone();
+    let routines = self.routines.len();
+    let outer = std::mem::replace(&mut self.locals, parameters.iter().map(|parameter| parameter.0.clone()).collect());
+    let function = self.function.take();
+    for ((_parameter, _type, _), _value) in parameters.iter().zip(&values) {
+      self.define(_parameter.clone(), _type, *_value);
+    }
+    if let (Token::FUNCTION, Some(Token::TYPE_SPEC(_type))) = (&routine.token, routine.children.get(2).map(|child| &child.token)) {
+      self.define(Interpreter::result(), _type, 0.0);
+      self.function = Some(name.clone());
+    }
+    let mut result = Ok(None);
+    self.depth += 1;
+    if let Some(_block) = routine.children.last() {
+      for _child in &_block.children {
+        match &_child.token {
+          Token::VAR => {
+            for _declaration in &_child.children {
+              self.locals.extend(_declaration.children.iter().map(|variable| variable.token.clone()));
+            }
+            result = self.declare(_child).map(|_| None);
+          },
+          // constants were replaced by the parser
+          Token::CONST => {},
+          Token::PROCEDURE | Token::FUNCTION => result = self.declare(_child).map(|_| None),
+          _ => result = self.execute(_child)
+        }
+        if result.is_err() {
+          break;
+        }
+      }
+    }
+    self.depth -= 1;
+    let value = if self.function.is_some() { self.lookup(&Interpreter::result()) } else { None };
+    let references: Vec<Option<f64>> = parameters.iter().map(|parameter| if parameter.2 { self.lookup(&parameter.0) } else { None }).collect();
+    let mut locals = std::mem::replace(&mut self.locals, outer);
+    locals.push(Interpreter::result());
+    self.function = function;
+    // every other variable keeps what the routine assigned to it
+    let assigned = std::mem::replace(&mut self.evaluator.assign_table, saved);
+    for (_token, _node) in assigned {
+      if !locals.iter().any(|local| names::same(local, &_token)) && matches!(_node.token, Token::REAL(_) | Token::INTEGER(_)) {
+        self.set(_token, _node);
+      }
+    }
+    self.routines.truncate(routines);
+    result?;
+    for (_target, _reference) in targets.into_iter().zip(references) {
+      if let (Some(_token), Some(_value)) = (_target, _reference) {
+        self.assign(_token, _value);
+      }
+    }
+    return Ok(value);
+  }
+  // resolve(AST) -> AST
+  fn resolve(&mut self, node: &AST) -> Result<AST, Diagnostic> {
+    // calls in an expression are replaced by their result
+    let call = match &node.token {
+      Token::CALL => Some(node.clone()),
+      // inside a function Result is its result, and its own name alone is a call as any other
+      Token::ID(_) if node.children.is_empty() && self.variable(node, false) != node.token => return Ok(AST::new(Interpreter::result(), vec![])),
+      // a function without parameters can be called by name alone
+      Token::ID(_) if node.children.is_empty() && self.lookup(&node.token).is_none() && self.routine(&node.token).is_some() => Some(AST::new(Token::CALL, vec![node.clone()])),
+      _ => None
+    };
+    if let Some(_call) = call {
+      match self.call(&_call)? {
+        Some(_value) => return Ok(AST::new(Token::REAL(_value), vec![])),
+        None => return Err(Diagnostic::new(format!("{} : {:?}", PANIC_EVAL, _call.children[0].token)))
+      }
+    }
+    let mut children = vec![];
+    for _child in &node.children {
+      children.push(self.resolve(_child)?);
+    }
+    return Ok(AST::new(node.token.clone(), children));
+  }
+  // evaluate(AST) -> f64
+  fn evaluate(&mut self, node: &AST) -> Result<f64, Diagnostic> {
+    let node = self.resolve(node)?;
+    return self.evaluator.evaluate(&node);
+  }
+  // condition(AST) -> bool
+  fn condition(&mut self, node: &AST) -> Result<bool, Diagnostic> {
+    return Ok(self.evaluate(node)? != 0.0);
+  }
+  // execute(AST) -> Option<f64>
+  fn execute(&mut self, node: &AST) -> Result<Option<f64>, Diagnostic> {
+    match &node.token {
+      Token::ASSIGN => {
+        let value = self.evaluate(&node.children[1])?;
+        let variable = self.variable(&node.children[0], true);
+        self.assign(variable.clone(), value);
+        return Ok(self.lookup(&variable));
+      },
+      Token::IF => {
+        if self.condition(&node.children[0])? {
+          self.execute(&node.children[1])?;
+        } else if node.children.len() as i32 > 2 {
+          self.execute(&node.children[2])?;
+        }
+        return Ok(None);
+      },
+      Token::WHILE => {
+        while self.condition(&node.children[0])? {
+          self.steps += 1;
+          if self.steps > MAX_STEPS {
+            return Err(Diagnostic::new(format!("{} : {:?}", PANIC_STEPS, node.token)));
+          }
+          self.execute(&node.children[1])?;
+        }
+        return Ok(None);
+      },
+      Token::BLOCK => {
+        for _child in &node.children {
+          self.execute(_child)?;
+        }
+        return Ok(None);
+      },
+      Token::VAR | Token::CONST | Token::PROCEDURE | Token::FUNCTION => {
+        self.declare(node)?;
+        return Ok(None);
+      },
+      Token::CALL => {
+        return self.call(node);
+      },
+      Token::EMPTY | Token::COMMENT(_) => {
+        return Ok(None);
+      },
+      _ => return Ok(Some(self.evaluate(node)?))
+    }
+  }
+  // run(AST) -> Option<f64>
+  pub fn run(&mut self, node: &AST) -> Result<Option<f64>, Diagnostic> {
+    self.steps = 0;
+    self.depth = 0;
+    return self.execute(node);
+  }
+}
+
+impl Default for Interpreter {
+  // default() -> Interpreter
+  fn default() -> Interpreter {
+    return Interpreter::new();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+
+  fn execute(interpreter: &mut Interpreter, source: &str) -> Result<Option<f64>, Diagnostic> {
+    let tree = Parser::new(Lexer::new(source.to_string())).unwrap().parse_statement().unwrap();
+    return interpreter.run(&tree);
+  }
+
+  fn declare(interpreter: &mut Interpreter, source: &str) {
+    let tree = Parser::new(Lexer::new(source.to_string())).unwrap().parse_declarations().unwrap();
+    interpreter.run(&tree).unwrap();
+  }
+
+  #[test]
+  fn run() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(execute(&mut interpreter, "x := 1;"), Ok(Some(1.0)));
+    assert_eq!(execute(&mut interpreter, "while x < 100 do x := x * 2;"), Ok(None));
+    assert_eq!(execute(&mut interpreter, "if x > 100 then y := 1 else y := 0;"), Ok(None));
+    assert_eq!(interpreter.variables(), vec![("x".to_string(), 128.0), ("y".to_string(), 1.0)]);
+    // names keep the spelling they were first assigned with
+    assert_eq!(execute(&mut interpreter, "X := x + Y;"), Ok(Some(129.0)));
+    assert_eq!(interpreter.variables(), vec![("x".to_string(), 129.0), ("y".to_string(), 1.0)]);
+    assert!(execute(&mut interpreter, "while 1 > 0 do x := x;").is_err());
+  }
+
+  #[test]
+  fn routines() {
+    // Result is the result of a function, not a variable left behind
+    let mut interpreter = Interpreter::new();
+    declare(&mut interpreter, "function Five: integer; begin Result := 5 end;");
+    assert_eq!(execute(&mut interpreter, "x := Five;"), Ok(Some(5.0)));
+    assert_eq!(interpreter.variables(), vec![("x".to_string(), 5.0)]);
+    // the name of a function alone is a call inside it, unless assigned to
+    execute(&mut interpreter, "k := 3;").unwrap();
+    declare(&mut interpreter, "function Down: integer; begin k := k - 1; if k > 0 then Down := Down + 1 end;");
+    assert_eq!(execute(&mut interpreter, "x := Down;"), Ok(Some(2.0)));
+    // a parameter or variable of that name hides the result
+    declare(&mut interpreter, "function Keep(result: integer): integer; begin result := result + 1; Keep := result end;");
+    assert_eq!(execute(&mut interpreter, "x := Keep(1);"), Ok(Some(2.0)));
+    declare(&mut interpreter, "procedure Add(var a: integer); begin a := a + 10 end; function Ten: integer; begin Add(Result) end;");
+    assert_eq!(execute(&mut interpreter, "x := Ten;"), Ok(Some(10.0)));
+  }
+
+  #[test]
+  fn integers() {
+    // integer variables are i32, reals are truncated and values out of range wrap around
+    let mut interpreter = Interpreter::new();
+    declare(&mut interpreter, "var i: integer; r: real;");
+    assert_eq!(execute(&mut interpreter, "i := 7 / 2;"), Ok(Some(3.0)));
+    assert_eq!(execute(&mut interpreter, "i := -7 / 2;"), Ok(Some(-3.0)));
+    assert_eq!(execute(&mut interpreter, "i := 2147483647 + 1;"), Ok(Some(-2147483648.0)));
+    assert_eq!(execute(&mut interpreter, "r := 7 / 2;"), Ok(Some(3.5)));
+    // as are integer parameters and results
+    declare(&mut interpreter, "function Half(n: integer): integer; begin Half := n / 2 end;");
+    assert_eq!(execute(&mut interpreter, "r := Half(7.5);"), Ok(Some(3.0)));
+  }
+
+  #[test]
+  fn limits() {
+    // endless recursion is an error
+    let mut interpreter = Interpreter::new();
+    declare(&mut interpreter, "procedure P; begin P end;");
+    assert_eq!(execute(&mut interpreter, "P;").unwrap_err().message, format!("{} : ID(\"P\")", PANIC_DEPTH));
+    // every call is a step, so a program that only calls stops as an endless loop does
+    declare(&mut interpreter, "procedure Q(n: integer); begin if n > 0 then begin Q(n - 1); Q(n - 1) end end;");
+    assert_eq!(execute(&mut interpreter, "Q(3);"), Ok(None));
+    assert_eq!(interpreter.steps, 15);
+    // recursion that ends is fine, and the limits start again on each run
+    declare(&mut interpreter, "function F(n: integer): integer; begin if n > 0 then F := n + F(n - 1) end;");
+    assert_eq!(execute(&mut interpreter, "x := F(60);"), Ok(Some(1830.0)));
+    assert_eq!(interpreter.steps, 61);
+  }
+}
@@ -1,97 +1,217 @@
-use crate::config::*;
+use std::collections::VecDeque;
+
 use crate::consts::*;
-use crate::token::{ Token };
+use crate::token::{ Token, Span, Trivia };
+use crate::diagnostic::Diagnostic;
+use crate::options::{ Dialect, Options };
 
 // characters
 const CHAR_DOT                  : char = '.';
 const CHAR_UNDERSCORE           : char = '_';
-// const CHAR_COMMA                : char = ',';
-const CHAR_COLON                : char = ':';
-const CHAR_SEMICOLON            : char = ';';
-const CHAR_EQUAL                : char = '=';
 const CHAR_PLUS                 : char = '+';
 const CHAR_MINUS                : char = '-';
 const CHAR_MULTIPLY             : char = '*';
 const CHAR_DIVIDE               : char = '/';
 const CHAR_LPAR                 : char = '(';
 const CHAR_RPAR                 : char = ')';
-// const CHAR_LBRA                 : char = '[';
-// const CHAR_RBRA                 : char = ']';
 const CHAR_LCUR                 : char = '{';
 const CHAR_RCUR                 : char = '}';
-const CHAR_GREATER_THAN         : char = '>';
-const CHAR_LESS_THAN            : char = '<';
 const CHAR_NEWLINE              : char = '\n';
-// keywords
-// const KEY_PROGRAM               : &str = "PROGRAM";
-// const KEY_CONST                 : &str = "CONST";
-// const KEY_VAR                   : &str = "VAR";
-// const KEY_DIV                   : &str = "DIV";
-// const KEY_MOD                   : &str = "MOD";
-const KEY_ODD                   : &str = "ODD";
-const KEY_BEGIN                 : &str = "BEGIN";
-const KEY_END                   : &str = "END";
-// const KEY_INTEGER               : &str = "INTEGER";
-// const KEY_LONGINT               : &str = "LONGINT";
-// const KEY_SMALLINT              : &str = "SMALLINT";
-// const KEY_REAL                  : &str = "REAL";
-// const KEY_BOOLEAN               : &str = "BOOLEAN";
-// const KEY_TRUE                  : &str = "TRUE";
-// const KEY_FALSE                 : &str = "FALSE";
-// const KEY_PACKED                : &str = "PACKED";
-// const KEY_ARRAY                 : &str = "ARRAY";
-// const KEY_OF                    : &str = "OF";
-const KEY_WHILE                 : &str = "WHILE";
-const KEY_DO                    : &str = "DO";
-const KEY_IF                    : &str = "IF";
-const KEY_THEN                  : &str = "THEN";
-const KEY_ELSE                  : &str = "ELSE";
-// functions
-// const FUNC_WRITELN              : &str = "WRITELN";
+const CHAR_QUOTE                : char = '\'';
+const CHAR_HASH                 : char = '#';
+const CHAR_DOLLAR               : char = '$';
+const CHAR_AMPERSAND            : char = '&';
+const CHAR_PERCENT              : char = '%';
+const CHAR_EXPONENT             : char = 'e';
+const CHAR_EXPONENT_UPPER       : char = 'E';
+// operators and punctuation, two characters before one
+const OPERATORS: &[(&str, Token)] = &[
+  (":=", Token::ASSIGN),
+  ("<>", Token::NOT_EQUAL),
+  ("<=", Token::LESS_EQUAL),
+  (">=", Token::GREATER_EQUAL),
+  ("..", Token::RANGE),
+  ("+", Token::PLUS),
+  ("-", Token::MINUS),
+  ("*", Token::MULTIPLY),
+  ("/", Token::DIVIDE),
+  ("=", Token::EQUAL),
+  ("<", Token::LESS_THAN),
+  (">", Token::GREATER_THAN),
+  ("(", Token::LPAR),
+  (")", Token::RPAR),
+  ("[", Token::LBRA),
+  ("]", Token::RBRA),
+  (";", Token::SEMICOLON),
+  (":", Token::COLON),
+  (",", Token::COMMA),
+  (".", Token::DOT),
+  ("^", Token::CARET),
+  ("@", Token::AT),
+];
+// predeclared names the parser treats as keywords
+const KEYWORDS: &[(&str, Token)] = &[
+  ("ODD", Token::ODD),
+  ("TRUE", Token::TRUE),
+  ("FALSE", Token::FALSE),
+];
+// reserved words in ISO 7185
+const KEYWORDS_ISO: &[(&str, Token)] = &[
+  ("AND", Token::AND),
+  ("ARRAY", Token::ARRAY),
+  ("BEGIN", Token::BEGIN),
+  ("CASE", Token::CASE),
+  ("CONST", Token::CONST),
+  ("DIV", Token::INTEGER_DIV),
+  ("DO", Token::DO),
+  ("DOWNTO", Token::DOWNTO),
+  ("ELSE", Token::ELSE),
+  ("END", Token::END),
+  ("FILE", Token::FILE),
+  ("FOR", Token::FOR),
+  ("FUNCTION", Token::FUNCTION),
+  ("GOTO", Token::GOTO),
+  ("IF", Token::IF),
+  ("IN", Token::IN),
+  ("LABEL", Token::LABEL),
+  ("MOD", Token::INTEGER_MOD),
+  ("NIL", Token::NIL),
+  ("NOT", Token::NOT),
+  ("OF", Token::OF),
+  ("OR", Token::OR),
+  ("PACKED", Token::PACKED),
+  ("PROCEDURE", Token::PROCEDURE),
+  ("PROGRAM", Token::PROGRAM),
+  ("RECORD", Token::RECORD),
+  ("REPEAT", Token::REPEAT),
+  ("SET", Token::SET),
+  ("THEN", Token::THEN),
+  ("TO", Token::TO),
+  ("TYPE", Token::TYPE),
+  ("UNTIL", Token::UNTIL),
+  ("VAR", Token::VAR),
+  ("WHILE", Token::WHILE),
+  ("WITH", Token::WITH),
+];
+// reserved words added by turbo pascal
+const KEYWORDS_TURBO: &[(&str, Token)] = &[
+  ("ASM", Token::ASM),
+  ("CONSTRUCTOR", Token::CONSTRUCTOR),
+  ("DESTRUCTOR", Token::DESTRUCTOR),
+  ("IMPLEMENTATION", Token::IMPLEMENTATION),
+  ("INHERITED", Token::INHERITED),
+  ("INLINE", Token::INLINE),
+  ("INTERFACE", Token::INTERFACE),
+  ("OBJECT", Token::OBJECT),
+  ("SHL", Token::SHL),
+  ("SHR", Token::SHR),
+  ("STRING", Token::STRING_TYPE),
+  ("UNIT", Token::UNIT),
+  ("USES", Token::USES),
+  ("XOR", Token::XOR),
+];
+// reserved words added by delphi
+const KEYWORDS_DELPHI: &[(&str, Token)] = &[
+  ("AS", Token::AS),
+  ("CLASS", Token::CLASS),
+  ("EXCEPT", Token::EXCEPT),
+  ("EXPORTS", Token::EXPORTS),
+  ("FINALIZATION", Token::FINALIZATION),
+  ("FINALLY", Token::FINALLY),
+  ("INITIALIZATION", Token::INITIALIZATION),
+  ("IS", Token::IS),
+  ("LIBRARY", Token::LIBRARY),
+  ("PROPERTY", Token::PROPERTY),
+  ("RAISE", Token::RAISE),
+  ("RESOURCESTRING", Token::RESOURCESTRING),
+  ("THREADVAR", Token::THREADVAR),
+  ("TRY", Token::TRY),
+];
+
+// keywords(Dialect) -> Vec<&[(&str, Token)]>
+fn keywords(dialect: Dialect) -> Vec<&'static [(&'static str, Token)]> {
+  // each dialect reserves the words of the one before it
+  match dialect {
+    Dialect::ISO => return vec![KEYWORDS, KEYWORDS_ISO],
+    Dialect::TURBO => return vec![KEYWORDS, KEYWORDS_ISO, KEYWORDS_TURBO],
+    Dialect::DELPHI => return vec![KEYWORDS, KEYWORDS_ISO, KEYWORDS_TURBO, KEYWORDS_DELPHI],
+  }
+}
 
 pub struct Lexer {
   text: String,
+  // characters with their byte offset in text
+  chars: Vec<(usize, char)>,
+  // index of current_char in chars
   position: i32,
+  // line and column of current_char
+  line: usize,
+  column: usize,
+  // start of the token being lexed
+  start: Span,
+  // span of the last token returned
+  span: Span,
+  // tokens lexed ahead by peek
+  buffer: VecDeque<(Span, Token, Vec<Trivia>)>,
+  // whitespace and comments read before the token being lexed, kept with the trivia option
+  pending: Vec<Trivia>,
+  // whitespace and comments before the last token returned
+  trivia: Vec<Trivia>,
+  // errors found so far, each one also returned as an ERROR token
+  diagnostics: Vec<Diagnostic>,
   current_char: Option<char>,
   comment_multiline: bool,
+  options: Options,
 }
 
 impl Lexer {
   // new(String) -> Lexer
   pub fn new(text: String) -> Lexer {
+    return Lexer::with_options(text, &Options::default());
+  }
+  // with_options(String, Options) -> Lexer
+  pub fn with_options(text: String, options: &Options) -> Lexer {
     let mut lexer = Lexer {
+      chars: text.char_indices().collect(),
       text: text,
       position: 0,
+      line: 1,
+      column: 1,
+      start: Span::new(0, 0, 1, 1),
+      span: Span::new(0, 0, 1, 1),
+      buffer: VecDeque::new(),
+      pending: vec![],
+      trivia: vec![],
+      diagnostics: vec![],
       current_char: None,
       comment_multiline: false,
+      options: options.clone(),
     };
-    if lexer.text.len() > 0 {
-      lexer.current_char = Some(lexer.text.as_bytes()[0] as char);
-    }
+    lexer.current_char = lexer.chars.first().map(|(_, _char)| *_char);
     return lexer;
   }
   // look_ahead() -> Option<char>
-  fn look_ahead(&mut self) -> Option<char> {
-    // next position in text
-    let next_position = self.position as i32 + 1;
-    if next_position > self.text.len() as i32 - 1 {
-      return None;
-    } else {
-      return Some(self.text.as_bytes()[next_position as usize] as char);
-    }
+  fn look_ahead(&self) -> Option<char> {
+    return self.look_ahead_at(1);
+  }
+  // look_ahead_at(usize) -> Option<char>
+  fn look_ahead_at(&self, offset: usize) -> Option<char> {
+    // position in chars
+    let next_position = self.position as usize + offset;
+    return self.chars.get(next_position).map(|(_, _char)| *_char);
   }
   // next_token()
   fn next_token(&mut self) {
-    if DEBUG && DEBUG_SHOW_CHAR { println!("{}{}{:?}", FORMAT_TAB, FORMAT_SPACE.repeat(2), self.current_char); }
-    self.position += 1;
-    // EOF
-    if self.position > self.text.len() as i32 - 1 {
-      self.current_char = None;
-    }
-    else {
-      // usize is specific to target machine
-      self.current_char = Some(self.text.as_bytes()[self.position as usize] as char);
+    if self.options.dump_chars { eprintln!("{}{}{:?}", FORMAT_TAB, FORMAT_SPACE.repeat(2), self.current_char); }
+    if self.current_char == Some(CHAR_NEWLINE) {
+      self.line += 1;
+      self.column = 1;
+    } else {
+      self.column += 1;
     }
+    self.position += 1;
+    // None is EOF
+    self.current_char = self.chars.get(self.position as usize).map(|(_, _char)| *_char);
   }
   // skip_whitespace() TODO: rename to whitespace()
   fn skip_whitespace(&mut self) {
@@ -104,19 +224,35 @@ impl Lexer {
     }
   }
   // skip_comment() TODO: rename to comment()
-  fn skip_comment(&mut self) {
+  fn skip_comment(&mut self) -> Result<(), Diagnostic> {
     if self.comment_multiline == true {
-      // next_token until closing curly
+      let start = self.here();
+      // (* ... *) or { ... }
+      let parens = self.current_char == Some(CHAR_LPAR);
+      let mut depth = 0;
       while let Some(_char) = self.current_char {
-        if _char != CHAR_RCUR {
+        let opening = if parens { _char == CHAR_LPAR && self.look_ahead() == Some(CHAR_MULTIPLY) } else { _char == CHAR_LCUR };
+        let closing = if parens { _char == CHAR_MULTIPLY && self.look_ahead() == Some(CHAR_RPAR) } else { _char == CHAR_RCUR };
+        if opening {
+          // comments of the same kind only nest with the option
+          if depth == 0 || self.options.nested_comments {
+            depth += 1;
+          }
           self.next_token();
+          if parens { self.next_token(); }
+        } else if closing {
+          depth -= 1;
+          self.next_token();
+          if parens { self.next_token(); }
+          if depth == 0 {
+            self.comment_multiline = false;
+            return Ok(());
+          }
         } else {
-          self.comment_multiline = false;
-          // closing curly brace
           self.next_token();
-          break;
         }
       }
+      return Err(Diagnostic::with_span(format!("{}", PANIC_COMMENT), Span::new(start.start, self.offset(), start.line, start.column)));
     } else {
       // next_token until newline
       while let Some(_char) = self.current_char {
@@ -129,56 +265,147 @@ impl Lexer {
         }
       }
     }
+    return Ok(());
   }
-  // number() -> Token
-  fn number(&mut self) -> Token {
-    let mut number = String::new();
+  // directive() -> Result<Token, Diagnostic>
+  fn directive(&mut self) -> Result<Token, Diagnostic> {
+    // a directive is a comment, the token is the text after the dollar sign
+    let parens = self.current_char == Some(CHAR_LPAR);
+    self.comment_multiline = true;
+    self.skip_comment()?;
+    let span = self.lexeme();
+    let (open, close) = if parens { (3, 2) } else { (2, 1) };
+    return Ok(Token::DIRECTIVE(self.text[span.start + open..span.end - close].trim().to_string()));
+  }
+  // digits(u32) -> String
+  fn digits(&mut self, radix: u32) -> String {
+    let mut digits = String::new();
     while let Some(_char) = self.current_char {
-      if _char.is_digit(10) {
-        number.push(_char);
+      if _char.is_digit(radix) {
+        digits.push(_char);
         self.next_token();
       } else {
         break;
       }
     }
-    // two dots is range
-    if self.current_char == Some(CHAR_DOT) && self.look_ahead() == Some(CHAR_DOT) {
+    return digits;
+  }
+  // integer(String, &str, u32) -> Result<Token, Diagnostic>
+  fn integer(&mut self, literal: String, digits: &str, radix: u32) -> Result<Token, Diagnostic> {
+    if digits.is_empty() {
+      return Err(Diagnostic::with_span(format!("{} : {}", literal, PANIC_SYNTAX), self.lexeme()));
+    }
+    // only overflow is left once digits are checked
+    match i64::from_str_radix(digits, radix) {
+      // $, & and % literals are the bits of a 32-bit integer, so $FFFFFFFF is -1
+      Ok(_int) if radix != 10 && _int <= u32::MAX as i64 => return Ok(Token::INTEGER(_int as u32 as i32 as i64)),
+      Ok(_int) => return Ok(Token::INTEGER(_int)),
+      Err(_) => return Err(Diagnostic::with_span(format!("{} : {}", literal, PANIC_INTEGER_RANGE), self.lexeme()))
+    }
+  }
+  // number() -> Result<Token, Diagnostic>
+  fn number(&mut self) -> Result<Token, Diagnostic> {
+    // $FF is hexadecimal, &17 is octal, %1010 is binary
+    let radix = match self.current_char {
+      Some(CHAR_DOLLAR) => 16,
+      Some(CHAR_AMPERSAND) => 8,
+      Some(CHAR_PERCENT) => 2,
+      _ => 10
+    };
+    if radix != 10 {
+      let prefix = self.current_char.unwrap();
       self.next_token();
+      let digits = self.digits(radix);
+      return self.integer(format!("{}{}", prefix, digits), &digits, radix);
+    }
+    let mut number = self.digits(10);
+    let mut real = false;
+    // one dot followed by a digit is floating-point
+    if self.current_char == Some(CHAR_DOT) && self.look_ahead().is_some_and(|_char| _char.is_digit(10)) {
+      real = true;
+      number.push(CHAR_DOT);
       self.next_token();
-      let mut end = String::new();
-      while let Some(_char) = self.current_char {
-        // check if char is digit
-        if _char.is_digit(10) {
-          // push to number
-          end.push(_char);
+      number.push_str(&self.digits(10));
+    }
+    // exponent -> E, E+ or E- followed by a digit
+    if let Some(CHAR_EXPONENT) | Some(CHAR_EXPONENT_UPPER) = self.current_char {
+      let sign = self.look_ahead() == Some(CHAR_PLUS) || self.look_ahead() == Some(CHAR_MINUS);
+      let digit = if sign { self.look_ahead_at(2) } else { self.look_ahead() };
+      if digit.is_some_and(|_char| _char.is_digit(10)) {
+        real = true;
+        number.push(CHAR_EXPONENT);
+        self.next_token();
+        if sign {
+          number.push(self.current_char.unwrap());
           self.next_token();
-        } else {
-          break;
         }
+        number.push_str(&self.digits(10));
       }
-      return Token::RANGE(number.parse::<i32>().unwrap(), end.parse::<i32>().unwrap());
     }
-    // one dot is floating-point
-    if self.current_char == Some(CHAR_DOT) {
-      // push to number
-      number.push(CHAR_DOT);
-      // next_token
-      self.next_token();
-      // next_token until not number
-      while let Some(_char) = self.current_char {
-        // check if char is digit
-        if _char.is_digit(10) {
-          // push to number
-          number.push(_char);
-          // next_token
+    if real {
+      match number.parse::<f64>() {
+        Ok(_float) => return Ok(Token::REAL(_float)),
+        Err(_) => return Err(Diagnostic::with_span(format!("{} : {}", number, PANIC_SYNTAX), self.lexeme()))
+      }
+    }
+    return self.integer(number.clone(), &number, 10);
+  }
+  // string() -> Result<Token, Diagnostic>
+  fn string(&mut self) -> Result<Token, Diagnostic> {
+    // quoted strings and character codes next to each other are one literal, 'a'#13#10'b'
+    let mut string = String::new();
+    loop {
+      match self.current_char {
+        Some(CHAR_QUOTE) => {
           self.next_token();
-        } else {
-          break;
-        }
+          loop {
+            match self.current_char {
+              // two quotes is one quote
+              Some(CHAR_QUOTE) if self.look_ahead() == Some(CHAR_QUOTE) => {
+                string.push(CHAR_QUOTE);
+                self.next_token();
+                self.next_token();
+              },
+              Some(CHAR_QUOTE) => {
+                self.next_token();
+                break;
+              },
+              // strings cannot span lines
+              None | Some(CHAR_NEWLINE) => return Err(Diagnostic::with_span(format!("{}", PANIC_STRING), self.lexeme())),
+              Some(_char) => {
+                string.push(_char);
+                self.next_token();
+              }
+            }
+          }
+        },
+        Some(CHAR_HASH) => {
+          self.next_token();
+          // #$hh is hexadecimal, #nn is decimal
+          let radix = if self.current_char == Some(CHAR_DOLLAR) { self.next_token(); 16 } else { 10 };
+          let mut code = String::new();
+          while let Some(_char) = self.current_char {
+            if _char.is_digit(radix) {
+              code.push(_char);
+              self.next_token();
+            } else {
+              break;
+            }
+          }
+          match u32::from_str_radix(&code, radix).ok().and_then(char::from_u32) {
+            Some(_char) => string.push(_char),
+            None => return Err(Diagnostic::with_span(format!("#{}{} : {}", if radix == 16 { "$" } else { "" }, code, PANIC_CHAR_CODE), self.lexeme()))
+          }
+        },
+        _ => break
       }
-      return Token::REAL(number.parse::<f64>().unwrap());
     }
-    return Token::INTEGER(number.parse::<i32>().unwrap());
+    // a single character is a char literal
+    let mut chars = string.chars();
+    match (chars.next(), chars.next()) {
+      (Some(_char), None) => return Ok(Token::CHAR(_char)),
+      _ => return Ok(Token::STRING(string))
+    }
   }
   // id() -> Token
   fn id(&mut self) -> Token {
@@ -191,207 +418,175 @@ impl Lexer {
         break;
       }
     }
-    // keywords
-    match string.to_uppercase().as_str() {
-      // KEY_PROGRAM => {
-      //   return Token::PROGRAM;
-      // },
-      // KEY_VAR => {
-      //   return Token::VAR;
-      // },
-      // KEY_CONST => {
-      //   return Token::CONST;
-      // },
-      // KEY_DIV => {
-      //   return Token::INTEGER_DIV;
-      // },
-      // KEY_MOD => {
-      //   return Token::INTEGER_MOD;
-      // },
-      KEY_ODD => {
-        return Token::ODD;
-      },
-      KEY_BEGIN => {
-        return Token::BEGIN;
-      },
-      KEY_END => {
-        return Token::END;
-      },
-      // KEY_INTEGER => {
-      //   return Token::TYPE_SPEC(Type::INTEGER);
-      // },
-      // KEY_LONGINT => {
-      //   return Token::TYPE_SPEC(Type::INTEGER);
-      // },
-      // KEY_SMALLINT => {
-      //   return Token::TYPE_SPEC(Type::INTEGER);
-      // },
-      // KEY_REAL => {
-      //   return Token::TYPE_SPEC(Type::REAL);
-      // },
-      // KEY_BOOLEAN => {
-      //   return Token::TYPE_SPEC(Type::BOOLEAN);
-      // },
-      // KEY_TRUE => {
-      //   return Token::TRUE;
-      // },
-      // KEY_FALSE => {
-      //   return Token::FALSE;
-      // },
-      // KEY_PACKED => {
-      //   return Token::PACKED;
-      // },
-      // KEY_ARRAY => {
-      //   return Token::ARRAY;
-      // },
-      // KEY_OF => {
-      //   return Token::OF;
-      // },
-      KEY_WHILE => {
-        return Token::WHILE;
-      },
-      KEY_DO => {
-        return Token::DO;
-      },
-      KEY_IF => {
-        return Token::IF;
-      },
-      KEY_THEN => {
-        return Token::THEN;
-      },
-      KEY_ELSE => {
-        return Token::ELSE;
-      },
-      // FUNC_WRITELN => {
-      //   return Token::WRITELN;
-      // },
-      _ => Token::ID(string)
+    // keywords are not case sensitive
+    let name = string.to_uppercase();
+    for _table in keywords(self.options.dialect) {
+      for (_keyword, _token) in _table.iter() {
+        if *_keyword == name {
+          return _token.clone();
+        }
+      }
     }
+    return Token::ID(string);
   }
-  // get_next_token() -> Token
-  pub fn get_next_token(&mut self) -> Token {
+  // starts_with(&str) -> bool
+  fn starts_with(&self, text: &str) -> bool {
+    let position = self.position as usize;
+    return text.chars().enumerate().all(|(_index, _char)| self.chars.get(position + _index).map(|(_, _next)| *_next) == Some(_char));
+  }
+  // keep(fn(String) -> Trivia, usize)
+  fn keep(&mut self, trivia: fn(String) -> Trivia, start: usize) {
+    // text from start to the current position is trivia of the next token
+    if self.options.trivia {
+      self.pending.push(trivia(self.text[start..self.offset()].to_string()));
+    }
+  }
+  // lex() -> Result<Token, Diagnostic>
+  fn lex(&mut self) -> Result<Token, Diagnostic> {
     while let Some(_char) = self.current_char {
       // whitespace
       if _char.is_whitespace() {
+        let start = self.offset();
         self.skip_whitespace();
+        self.keep(Trivia::WHITESPACE, start);
+        continue;
+      }
+      // directive -> {$ } or (*$ *)
+      if (_char == CHAR_LCUR && self.look_ahead() == Some(CHAR_DOLLAR)) || (_char == CHAR_LPAR && self.look_ahead() == Some(CHAR_MULTIPLY) && self.look_ahead_at(2) == Some(CHAR_DOLLAR)) {
+        self.start = self.here();
+        return self.directive();
+      }
+      // multiline comment -> { } or (* *)
+      if _char == CHAR_LCUR || (_char == CHAR_LPAR && self.look_ahead() == Some(CHAR_MULTIPLY)) {
+        let start = self.offset();
+        self.comment_multiline = true;
+        self.skip_comment()?;
+        self.keep(Trivia::COMMENT, start);
         continue;
       }
-      // multiline comment
-      // if _char == CHAR_LCUR {
-      //   self.comment_multiline = true;
-      //   self.next_token();
-      //   self.skip_comment();
-      //   continue;
-      // }
       // comment -> //
       if _char == CHAR_DIVIDE && self.look_ahead() == Some(CHAR_DIVIDE) {
+        let start = self.offset();
         self.next_token();
-        self.skip_comment();
+        self.skip_comment()?;
+        self.keep(Trivia::COMMENT, start);
         continue; 
       }
+      // start of token
+      self.start = self.here();
       // identifier -> [a-zA-Z]
       if _char.is_alphabetic() {
-        return self.id();
+        return Ok(self.id());
       }
-      // number -> [0-9]
+      // number -> [0-9], $[0-9A-F], &[0-7] or %[01]
       // base-10 is decimal number
       if _char.is_digit(10) {
         return self.number();
       }
-      // assignment -> :=
-      if _char == CHAR_COLON && self.look_ahead() == Some(CHAR_EQUAL) {
-        self.next_token();
-        self.next_token();
-        return Token::ASSIGN;
+      if (_char == CHAR_DOLLAR || _char == CHAR_AMPERSAND || _char == CHAR_PERCENT) && self.look_ahead().is_some() {
+        return self.number();
       }
-      // not equal -> <>
-      // if _char == CHAR_LESS_THAN && self.look_ahead() == Some(CHAR_GREATER_THAN) {
-      //   self.next_token();
-      //   self.next_token();
-      //   return Token::NOT_EQUAL;
-      // }
-      // less than or equal -> <=
-      // if _char == CHAR_LESS_THAN && self.look_ahead() == Some(CHAR_EQUAL) {
-      //   self.next_token();
-      //   self.next_token();
-      //   return Token::LESS_EQUAL;
-      // }
-      // greater than or equal -> >=
-      // if _char == CHAR_GREATER_THAN && self.look_ahead() == Some(CHAR_EQUAL) {
-      //   self.next_token();
-      //   self.next_token();
-      //   return Token::GREATER_EQUAL;
-      // }
-      // colon -> :
-      // if _char == CHAR_COLON {
-      //   self.next_token();
-      //   return Token::COLON;
-      // }
-      // semicolon -> ;
-      if _char == CHAR_SEMICOLON {
-        self.next_token();
-        return Token::SEMICOLON;
+      // string -> 'text' or #nn
+      if _char == CHAR_QUOTE || _char == CHAR_HASH {
+        return self.string();
       }
-      // comma -> ,
-      // if _char == CHAR_COMMA {
-      //   self.next_token();
-      //   return Token::COMMA;
-      // }
-      // dot -> .
-      // if _char == CHAR_DOT {
-      //   self.current_char = None;
-      //   return Token::DOT;
-      // }
-      // operators
-      match _char {
-        CHAR_PLUS => {
-          self.next_token();
-          return Token::PLUS;
-        },
-        CHAR_MINUS => {
-          self.next_token();
-          return Token::MINUS;
-        },
-        CHAR_MULTIPLY => {
-          self.next_token();
-          return Token::MULTIPLY;
-        },
-        CHAR_DIVIDE => {
-          self.next_token();
-          return Token::DIVIDE;
-        },
-        CHAR_LPAR => {
-          self.next_token();
-          return Token::LPAR;
-        },
-        CHAR_RPAR => {
-          self.next_token();
-          return Token::RPAR;
-        },
-        // CHAR_LBRA => {
-        //   self.next_token();
-        //   return Token::LBRA;
-        // },
-        // CHAR_RBRA => {
-        //   self.next_token();
-        //   return Token::RBRA;
-        // },
-        CHAR_EQUAL => {
-          self.next_token();
-          return Token::EQUAL;
-        },
-        CHAR_GREATER_THAN => {
-          self.next_token();
-          return Token::GREATER_THAN;
-        },
-        CHAR_LESS_THAN => {
-          self.next_token();
-          return Token::LESS_THAN;
-        },
-        _ => panic!("{} : {}", _char.to_string(), PANIC_SYNTAX)
+      // operators, longest first
+      for (_operator, _token) in OPERATORS {
+        if self.starts_with(_operator) {
+          for _ in _operator.chars() {
+            self.next_token();
+          }
+          return Ok(_token.clone());
+        }
+      }
+      // skip the character so the next token starts after it
+      self.next_token();
+      return Err(Diagnostic::with_span(format!("{} : {}", _char, PANIC_SYNTAX), self.lexeme()));
+    }
+    self.start = self.here();
+    return Ok(Token::EOF);
+  }
+  // scan() -> (Span, Token, Vec<Trivia>)
+  fn scan(&mut self) -> (Span, Token, Vec<Trivia>) {
+    let token = self.lex();
+    let trivia = std::mem::take(&mut self.pending);
+    match token {
+      Ok(_token) => return (self.lexeme(), _token, trivia),
+      Err(why) => {
+        // every error has moved past the bad text, so lexing goes on from here
+        let span = why.span.unwrap_or(self.lexeme());
+        self.diagnostics.push(why);
+        return (span, Token::ERROR(self.text[span.start..span.end].to_string()), trivia);
       }
     }
-    Token::EOF
+  }
+  // fill(usize)
+  fn fill(&mut self, count: usize) {
+    while self.buffer.len() < count {
+      let token = self.scan();
+      self.buffer.push_back(token);
+    }
+  }
+  // peek(usize) -> Token
+  pub fn peek(&mut self, offset: usize) -> Token {
+    // peek(0) is the token get_next_token returns next, EOF repeats at the end
+    self.fill(offset + 1);
+    return self.buffer[offset].1.clone();
+  }
+  // peek_span(usize) -> Span
+  pub fn peek_span(&mut self, offset: usize) -> Span {
+    self.fill(offset + 1);
+    return self.buffer[offset].0;
+  }
+  // get_next_token() -> Token
+  pub fn get_next_token(&mut self) -> Token {
+    self.fill(1);
+    let (span, token, trivia) = self.buffer.pop_front().unwrap();
+    self.span = span;
+    self.trivia = trivia;
+    return token;
+  }
+  // trivia() -> &[Trivia]
+  pub fn trivia(&self) -> &[Trivia] {
+    // empty unless the trivia option is set
+    return &self.trivia;
+  }
+  // diagnostics() -> &[Diagnostic]
+  pub fn diagnostics(&self) -> &[Diagnostic] {
+    return &self.diagnostics;
+  }
+  // offset() -> usize
+  fn offset(&self) -> usize {
+    // byte offset of current_char, or length of text at EOF
+    match self.chars.get(self.position as usize) {
+      Some((_offset, _)) => return *_offset,
+      None => return self.text.len()
+    }
+  }
+  // here() -> Span
+  fn here(&self) -> Span {
+    return Span::new(self.offset(), self.offset(), self.line, self.column);
+  }
+  // lexeme() -> Span
+  fn lexeme(&self) -> Span {
+    // the lexer stops right after a token, so the current position is where the token being lexed ends
+    return Span::new(self.start.start, self.offset(), self.start.line, self.start.column);
+  }
+  // span() -> Span
+  pub fn span(&self) -> Span {
+    return self.span;
+  }
+}
+
+impl Iterator for Lexer {
+  type Item = Token;
+  // next() -> Option<Token>
+  fn next(&mut self) -> Option<Token> {
+    // errors are ERROR tokens, so only EOF ends the tokens
+    match self.get_next_token() {
+      Token::EOF => return None,
+      _token => return Some(_token)
+    }
   }
 }
 
@@ -407,11 +602,25 @@ mod tests {
     assert_eq!(lexer.current_char, Some('4'));
   }
 
+  #[test]
+  fn utf8() {
+    let mut lexer = Lexer::new("// hej då\nsmörgås := ö;".to_string());
+    assert_eq!(lexer.get_next_token(), Token::ID("smörgås".to_string()));
+    assert_eq!(lexer.span(), Span::new(11, 20, 2, 1));
+    assert_eq!(lexer.get_next_token(), Token::ASSIGN);
+    assert_eq!(lexer.span(), Span::new(21, 23, 2, 9));
+    assert_eq!(lexer.get_next_token(), Token::ID("ö".to_string()));
+    assert_eq!(lexer.span(), Span::new(24, 26, 2, 12));
+    assert_eq!(lexer.get_next_token(), Token::SEMICOLON);
+    assert_eq!(lexer.get_next_token(), Token::EOF);
+    assert_eq!(lexer.span(), Span::new(27, 27, 2, 14));
+  }
+
   #[test]
   fn look_ahead() {
-    let mut lexer = Lexer::new("42".to_string());
+    let lexer = Lexer::new("42".to_string());
     assert_eq!(lexer.look_ahead(), Some('2'));
-    let mut lexer = Lexer::new("1".to_string());
+    let lexer = Lexer::new("1".to_string());
     assert_eq!(lexer.look_ahead(), None);
   }
 
@@ -435,36 +644,153 @@ mod tests {
     assert_eq!(lexer.current_char, Some('2'));
   }
 
-  // #[test]
-  // fn skip_comment() {
-  //   let mut lexer = Lexer::new("{ 2 }".to_string());
-  //   assert_eq!(lexer.current_char, Some('{'));
-  //   lexer.skip_comment();
-  //   assert_eq!(lexer.current_char, None);
-  // }
+  #[test]
+  fn skip_comment() {
+    let mut lexer = Lexer::new("{ 2 }".to_string());
+    assert_eq!(lexer.current_char, Some('{'));
+    lexer.comment_multiline = true;
+    assert_eq!(lexer.skip_comment(), Ok(()));
+    assert_eq!(lexer.current_char, None);
+    let mut lexer = Lexer::new("(* { *) 1 { (* } 2".to_string());
+    assert_eq!(lexer.get_next_token(), Token::INTEGER(1));
+    assert_eq!(lexer.get_next_token(), Token::INTEGER(2));
+    // nested comments
+    let mut lexer = Lexer::new("{ { } } 1".to_string());
+    assert_eq!(lexer.get_next_token(), Token::ERROR("}".to_string()));
+    let mut lexer = Lexer::with_options("{ { } } (* (* *) *) 1".to_string(), &Options { nested_comments: true, ..Options::default() });
+    assert_eq!(lexer.get_next_token(), Token::INTEGER(1));
+    // unterminated comments
+    let mut lexer = Lexer::new("1\n  (* 2".to_string());
+    assert_eq!(lexer.get_next_token(), Token::INTEGER(1));
+    assert_eq!(lexer.get_next_token(), Token::ERROR("(* 2".to_string()));
+    let diagnostic = &lexer.diagnostics()[0];
+    assert_eq!(diagnostic.message, PANIC_COMMENT);
+    assert_eq!(diagnostic.span, Some(Span::new(4, 8, 2, 3)));
+  }
+
+  #[test]
+  fn directive() {
+    let mut lexer = Lexer::new("{$I file.inc} (*$R+*) { $I } 1".to_string());
+    assert_eq!(lexer.get_next_token(), Token::DIRECTIVE("I file.inc".to_string()));
+    assert_eq!(lexer.span(), Span::new(0, 13, 1, 1));
+    assert_eq!(lexer.get_next_token(), Token::DIRECTIVE("R+".to_string()));
+    assert_eq!(lexer.get_next_token(), Token::INTEGER(1));
+    let mut lexer = Lexer::new("{$IFDEF X".to_string());
+    assert_eq!(lexer.get_next_token(), Token::ERROR("{$IFDEF X".to_string()));
+  }
+
+  #[test]
+  fn trivia() {
+    let source = "{ doc } x :=  1; // one\n(* end *)";
+    let options = Options { trivia: true, ..Options::default() };
+    let mut lexer = Lexer::with_options(source.to_string(), &options);
+    assert_eq!(lexer.get_next_token(), Token::ID("x".to_string()));
+    assert_eq!(lexer.trivia(), &[Trivia::COMMENT("{ doc }".to_string()), Trivia::WHITESPACE(" ".to_string())]);
+    // the tokens and their trivia are the source
+    let mut text = String::new();
+    let mut lexer = Lexer::with_options(source.to_string(), &options);
+    loop {
+      let token = lexer.get_next_token();
+      for _trivia in lexer.trivia() {
+        match _trivia {
+          Trivia::WHITESPACE(_text) | Trivia::COMMENT(_text) => text.push_str(_text),
+        }
+      }
+      text.push_str(&source[lexer.span().start..lexer.span().end]);
+      if token == Token::EOF {
+        break;
+      }
+    }
+    assert_eq!(text, source);
+    let mut lexer = Lexer::new(source.to_string());
+    assert_eq!(lexer.get_next_token(), Token::ID("x".to_string()));
+    assert!(lexer.trivia().is_empty());
+  }
 
   #[test]
   fn number() {
     let mut lexer = Lexer::new("42".to_string());
-    assert_eq!(lexer.number(), Token::INTEGER(42));
+    assert_eq!(lexer.number(), Ok(Token::INTEGER(42)));
     let mut lexer = Lexer::new("4.2".to_string());
-    assert_eq!(lexer.number(), Token::REAL(4.2));
+    assert_eq!(lexer.number(), Ok(Token::REAL(4.2)));
     let mut lexer = Lexer::new("4..2".to_string());
-    assert_eq!(lexer.number(), Token::RANGE(4, 2));
+    assert_eq!(lexer.get_next_token(), Token::INTEGER(4));
+    assert_eq!(lexer.get_next_token(), Token::RANGE);
+    assert_eq!(lexer.get_next_token(), Token::INTEGER(2));
+    let mut lexer = Lexer::new("1.5E-3".to_string());
+    assert_eq!(lexer.number(), Ok(Token::REAL(1.5E-3)));
+    let mut lexer = Lexer::new("2e10".to_string());
+    assert_eq!(lexer.number(), Ok(Token::REAL(2e10)));
+    let mut lexer = Lexer::new("3000000000".to_string());
+    assert_eq!(lexer.number(), Ok(Token::INTEGER(3000000000)));
+    let mut lexer = Lexer::new("$FF".to_string());
+    assert_eq!(lexer.number(), Ok(Token::INTEGER(255)));
+    let mut lexer = Lexer::new("$FFFFFFFF".to_string());
+    assert_eq!(lexer.number(), Ok(Token::INTEGER(-1)));
+    let mut lexer = Lexer::new("$100000000".to_string());
+    assert_eq!(lexer.number(), Ok(Token::INTEGER(4294967296)));
+    let mut lexer = Lexer::new("&17".to_string());
+    assert_eq!(lexer.number(), Ok(Token::INTEGER(15)));
+    let mut lexer = Lexer::new("%1010".to_string());
+    assert_eq!(lexer.number(), Ok(Token::INTEGER(10)));
+    // not an exponent or a fraction
+    let mut lexer = Lexer::new("2 else".to_string());
+    assert_eq!(lexer.get_next_token(), Token::INTEGER(2));
+    let mut lexer = Lexer::new("2else".to_string());
+    assert_eq!(lexer.get_next_token(), Token::INTEGER(2));
+    assert_eq!(lexer.get_next_token(), Token::ELSE);
+    // out of range
+    let mut lexer = Lexer::new("x := 9223372036854775808;".to_string());
+    assert_eq!(lexer.get_next_token(), Token::ID("x".to_string()));
+    assert_eq!(lexer.get_next_token(), Token::ASSIGN);
+    assert_eq!(lexer.get_next_token(), Token::ERROR("9223372036854775808".to_string()));
+    let diagnostic = &lexer.diagnostics()[0];
+    assert_eq!(diagnostic.message, format!("9223372036854775808 : {}", PANIC_INTEGER_RANGE));
+    assert_eq!(diagnostic.span, Some(Span::new(5, 24, 1, 6)));
+    let mut lexer = Lexer::new("$".to_string());
+    assert_eq!(lexer.get_next_token(), Token::ERROR("$".to_string()));
+  }
+
+  #[test]
+  fn string() {
+    let mut lexer = Lexer::new("'Hello'".to_string());
+    assert_eq!(lexer.string(), Ok(Token::STRING("Hello".to_string())));
+    let mut lexer = Lexer::new("'it''s'".to_string());
+    assert_eq!(lexer.string(), Ok(Token::STRING("it's".to_string())));
+    let mut lexer = Lexer::new("''".to_string());
+    assert_eq!(lexer.string(), Ok(Token::STRING("".to_string())));
+    let mut lexer = Lexer::new("'a'".to_string());
+    assert_eq!(lexer.string(), Ok(Token::CHAR('a')));
+    let mut lexer = Lexer::new("''''".to_string());
+    assert_eq!(lexer.string(), Ok(Token::CHAR('\'')));
+    let mut lexer = Lexer::new("'Hello'#13#$0A'wörld'#33".to_string());
+    assert_eq!(lexer.string(), Ok(Token::STRING("Hello\r\nwörld!".to_string())));
+    let mut lexer = Lexer::new("#65".to_string());
+    assert_eq!(lexer.string(), Ok(Token::CHAR('A')));
+    let mut lexer = Lexer::new("#".to_string());
+    assert!(lexer.string().is_err());
+    let mut lexer = Lexer::new("x := 'abc;\ny".to_string());
+    assert_eq!(lexer.get_next_token(), Token::ID("x".to_string()));
+    assert_eq!(lexer.get_next_token(), Token::ASSIGN);
+    assert_eq!(lexer.get_next_token(), Token::ERROR("'abc;".to_string()));
+    assert_eq!(lexer.get_next_token(), Token::ID("y".to_string()));
+    let diagnostic = &lexer.diagnostics()[0];
+    assert_eq!(diagnostic.message, PANIC_STRING);
+    assert_eq!(diagnostic.span, Some(Span::new(5, 10, 1, 6)));
   }
 
   #[test]
   fn id() {
-    // let mut lexer = Lexer::new("PROGRAM".to_string());
-    // assert_eq!(lexer.id(), Token::PROGRAM);
-    // let mut lexer = Lexer::new("VAR".to_string());
-    // assert_eq!(lexer.id(), Token::VAR);
-    // let mut lexer = Lexer::new("CONST".to_string());
-    // assert_eq!(lexer.id(), Token::CONST);
-    // let mut lexer = Lexer::new("DIV".to_string());
-    // assert_eq!(lexer.id(), Token::INTEGER_DIV);
-    // let mut lexer = Lexer::new("MOD".to_string());
-    // assert_eq!(lexer.id(), Token::INTEGER_MOD);
+    let mut lexer = Lexer::new("PROGRAM".to_string());
+    assert_eq!(lexer.id(), Token::PROGRAM);
+    let mut lexer = Lexer::new("VAR".to_string());
+    assert_eq!(lexer.id(), Token::VAR);
+    let mut lexer = Lexer::new("CONST".to_string());
+    assert_eq!(lexer.id(), Token::CONST);
+    let mut lexer = Lexer::new("DIV".to_string());
+    assert_eq!(lexer.id(), Token::INTEGER_DIV);
+    let mut lexer = Lexer::new("MOD".to_string());
+    assert_eq!(lexer.id(), Token::INTEGER_MOD);
     let mut lexer = Lexer::new("BEGIN".to_string());
     assert_eq!(lexer.id(), Token::BEGIN);
     let mut lexer = Lexer::new("END".to_string());
@@ -479,16 +805,16 @@ mod tests {
     // assert_eq!(lexer.id(), Token::TYPE_SPEC(Type::REAL));
     // let mut lexer = Lexer::new("BOOLEAN".to_string());
     // assert_eq!(lexer.id(), Token::TYPE_SPEC(Type::BOOLEAN));
-    // let mut lexer = Lexer::new("TRUE".to_string());
-    // assert_eq!(lexer.id(), Token::TRUE);
-    // let mut lexer = Lexer::new("FALSE".to_string());
-    // assert_eq!(lexer.id(), Token::FALSE);
-    // let mut lexer = Lexer::new("PACKED".to_string());
-    // assert_eq!(lexer.id(), Token::PACKED);
-    // let mut lexer = Lexer::new("ARRAY".to_string());
-    // assert_eq!(lexer.id(), Token::ARRAY);
-    // let mut lexer = Lexer::new("OF".to_string());
-    // assert_eq!(lexer.id(), Token::OF);
+    let mut lexer = Lexer::new("TRUE".to_string());
+    assert_eq!(lexer.id(), Token::TRUE);
+    let mut lexer = Lexer::new("FALSE".to_string());
+    assert_eq!(lexer.id(), Token::FALSE);
+    let mut lexer = Lexer::new("PACKED".to_string());
+    assert_eq!(lexer.id(), Token::PACKED);
+    let mut lexer = Lexer::new("ARRAY".to_string());
+    assert_eq!(lexer.id(), Token::ARRAY);
+    let mut lexer = Lexer::new("OF".to_string());
+    assert_eq!(lexer.id(), Token::OF);
     let mut lexer = Lexer::new("WHILE".to_string());
     assert_eq!(lexer.id(), Token::WHILE);
     let mut lexer = Lexer::new("DO".to_string());
@@ -503,6 +829,53 @@ mod tests {
     // assert_eq!(lexer.id(), Token::WRITELN);
     let mut lexer = Lexer::new("identifier".to_string());
     assert_eq!(lexer.id(), Token::ID("identifier".to_string()));
+    let mut lexer = Lexer::new("begin".to_string());
+    assert_eq!(lexer.id(), Token::BEGIN);
+  }
+
+  #[test]
+  fn keywords() {
+    // reserved words depend on the dialect
+    let options = Options { dialect: Dialect::ISO, ..Options::default() };
+    let mut lexer = Lexer::with_options("unit class".to_string(), &options);
+    assert_eq!(lexer.get_next_token(), Token::ID("unit".to_string()));
+    assert_eq!(lexer.get_next_token(), Token::ID("class".to_string()));
+    let options = Options { dialect: Dialect::TURBO, ..Options::default() };
+    let mut lexer = Lexer::with_options("unit class".to_string(), &options);
+    assert_eq!(lexer.get_next_token(), Token::UNIT);
+    assert_eq!(lexer.get_next_token(), Token::ID("class".to_string()));
+    let options = Options { dialect: Dialect::DELPHI, ..Options::default() };
+    let mut lexer = Lexer::with_options("unit class".to_string(), &options);
+    assert_eq!(lexer.get_next_token(), Token::UNIT);
+    assert_eq!(lexer.get_next_token(), Token::CLASS);
+  }
+
+  #[test]
+  fn operators() {
+    let mut lexer = Lexer::new("<> <= >= < > = .. . , : := [ ] ^ @".to_string());
+    assert_eq!(lexer.get_next_token(), Token::NOT_EQUAL);
+    assert_eq!(lexer.get_next_token(), Token::LESS_EQUAL);
+    assert_eq!(lexer.get_next_token(), Token::GREATER_EQUAL);
+    assert_eq!(lexer.get_next_token(), Token::LESS_THAN);
+    assert_eq!(lexer.get_next_token(), Token::GREATER_THAN);
+    assert_eq!(lexer.get_next_token(), Token::EQUAL);
+    assert_eq!(lexer.get_next_token(), Token::RANGE);
+    assert_eq!(lexer.get_next_token(), Token::DOT);
+    assert_eq!(lexer.get_next_token(), Token::COMMA);
+    assert_eq!(lexer.get_next_token(), Token::COLON);
+    assert_eq!(lexer.get_next_token(), Token::ASSIGN);
+    assert_eq!(lexer.get_next_token(), Token::LBRA);
+    assert_eq!(lexer.get_next_token(), Token::RBRA);
+    assert_eq!(lexer.get_next_token(), Token::CARET);
+    assert_eq!(lexer.get_next_token(), Token::AT);
+    assert_eq!(lexer.get_next_token(), Token::EOF);
+    let mut lexer = Lexer::new("a[1..n]".to_string());
+    assert_eq!(lexer.get_next_token(), Token::ID("a".to_string()));
+    assert_eq!(lexer.get_next_token(), Token::LBRA);
+    assert_eq!(lexer.get_next_token(), Token::INTEGER(1));
+    assert_eq!(lexer.get_next_token(), Token::RANGE);
+    assert_eq!(lexer.get_next_token(), Token::ID("n".to_string()));
+    assert_eq!(lexer.get_next_token(), Token::RBRA);
   }
   
   #[test]
@@ -538,6 +911,46 @@ mod tests {
   //   assert_eq!(lexer.get_next_token(), Token::SEMICOLON);
   // }
 
+  #[test]
+  fn peek() {
+    let mut lexer = Lexer::new("x := 1".to_string());
+    assert_eq!(lexer.peek(1), Token::ASSIGN);
+    assert_eq!(lexer.peek_span(1), Span::new(2, 4, 1, 3));
+    assert_eq!(lexer.peek(0), Token::ID("x".to_string()));
+    assert_eq!(lexer.get_next_token(), Token::ID("x".to_string()));
+    assert_eq!(lexer.span(), Span::new(0, 1, 1, 1));
+    assert_eq!(lexer.peek(4), Token::EOF);
+    assert_eq!(lexer.get_next_token(), Token::ASSIGN);
+    assert_eq!(lexer.span(), Span::new(2, 4, 1, 3));
+  }
+
+  #[test]
+  fn iterator() {
+    let lexer = Lexer::new("x := 1;".to_string());
+    assert_eq!(lexer.collect::<Vec<Token>>(), vec![Token::ID("x".to_string()), Token::ASSIGN, Token::INTEGER(1), Token::SEMICOLON]);
+  }
+
+  #[test]
+  fn recover() {
+    let mut lexer = Lexer::new("x := \"a\" ? 1;".to_string());
+    assert_eq!(lexer.by_ref().collect::<Vec<Token>>(), vec![
+      Token::ID("x".to_string()),
+      Token::ASSIGN,
+      Token::ERROR("\"".to_string()),
+      Token::ID("a".to_string()),
+      Token::ERROR("\"".to_string()),
+      Token::ERROR("?".to_string()),
+      Token::INTEGER(1),
+      Token::SEMICOLON,
+    ]);
+    let messages: Vec<String> = lexer.diagnostics().iter().map(|diagnostic| diagnostic.to_string()).collect();
+    assert_eq!(messages, vec![
+      format!("1:6 : \" : {}", PANIC_SYNTAX),
+      format!("1:8 : \" : {}", PANIC_SYNTAX),
+      format!("1:10 : ? : {}", PANIC_SYNTAX),
+    ]);
+  }
+
   #[test]
   fn variable_assignment() {
     let mut lexer = Lexer::new("number := 42;".to_string());
@@ -0,0 +1,256 @@
+#![allow(dead_code)]
+#![allow(non_camel_case_types)]
+#![allow(unused_variables)]
+#![allow(unused_assignments)]
+
+pub mod ast;
+pub mod consts;
+pub mod diagnostic;
+pub mod dump;
+pub mod emitter;
+pub mod evaluator;
+pub mod interpreter;
+pub mod lexer;
+pub mod names;
+pub mod options;
+pub mod parser;
+pub mod preprocessor;
+pub mod project;
+pub mod repl;
+pub mod token;
+
+use crate::ast::AST;
+use crate::consts::*;
+use crate::diagnostic::{ Diagnostic, Diagnostics };
+use crate::emitter::Emitter;
+use crate::lexer::Lexer;
+use crate::options::Options;
+use crate::parser::Parser;
+use crate::token::{ Type, Token, Span };
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompiledModule {
+  // name of the program, exported as the entry point
+  pub name: String,
+  pub tree: AST,
+  pub symbols: Vec<(Token, Type)>,
+  pub wat: String,
+  // switch directives such as ("R", "+"), with the last setting of each
+  pub switches: Vec<(String, String)>,
+  // files read with {$I}
+  pub files: Vec<String>,
+}
+
+// parser(&str, &Options) -> Result<Parser, Diagnostic>
+fn parser(source: &str, options: &Options) -> Result<Parser, Diagnostic> {
+  return Parser::with_options(Lexer::with_options(source.to_string(), options), options);
+}
+
+// tokenize(&str, &Options) -> Result<Vec<Token>, Diagnostics>
+pub fn tokenize(source: &str, options: &Options) -> Result<Vec<Token>, Diagnostics> {
+  let mut lexer = Lexer::with_options(source.to_string(), options);
+  let tokens = lexer.by_ref().collect();
+  if !lexer.diagnostics().is_empty() {
+    return Err(Diagnostics::new(lexer.diagnostics().to_vec()));
+  }
+  return Ok(tokens);
+}
+
+// tokenize_with_spans(&str, &Options) -> Result<Vec<(Span, Token)>, Diagnostics>
+pub fn tokenize_with_spans(source: &str, options: &Options) -> Result<Vec<(Span, Token)>, Diagnostics> {
+  let mut lexer = Lexer::with_options(source.to_string(), options);
+  let mut tokens = vec![];
+  loop {
+    let token = lexer.get_next_token();
+    if token == Token::EOF {
+      break;
+    }
+    tokens.push((lexer.span(), token));
+  }
+  if !lexer.diagnostics().is_empty() {
+    return Err(Diagnostics::new(lexer.diagnostics().to_vec()));
+  }
+  return Ok(tokens);
+}
+
+// parse_program(&str, &Options) -> Result<AST, Diagnostics>
+pub fn parse_program(source: &str, options: &Options) -> Result<AST, Diagnostics> {
+  let mut parser = parser(source, options)?;
+  match parser.parse() {
+    Ok(_tree) => return Ok(_tree),
+    Err(why) => return Err(parser.diagnostics(why))
+  }
+}
+
+// parse_statement(&str, &Options) -> Result<AST, Diagnostics>
+pub fn parse_statement(source: &str, options: &Options) -> Result<AST, Diagnostics> {
+  let mut parser = parser(source, options)?;
+  match parser.parse_statement() {
+    Ok(_tree) => return Ok(_tree),
+    Err(why) => return Err(parser.diagnostics(why))
+  }
+}
+
+// parse_expression(&str, &Options) -> Result<AST, Diagnostics>
+pub fn parse_expression(source: &str, options: &Options) -> Result<AST, Diagnostics> {
+  let mut parser = parser(source, options)?;
+  match parser.parse_expression() {
+    Ok(_tree) => return Ok(_tree),
+    Err(why) => return Err(parser.diagnostics(why))
+  }
+}
+
+// emit(&AST, &Options) -> Result<String, Diagnostics>
+pub fn emit(tree: &AST, options: &Options) -> Result<String, Diagnostics> {
+  let mut emitter = Emitter::new(vec![], options);
+  emitter.emit(tree)?;
+  match String::from_utf8(emitter.into_inner()) {
+    Ok(_wat) => return Ok(_wat),
+    Err(why) => return Err(Diagnostics::from(Diagnostic::new(format!("{} : {}", PANIC_COMPILE, why))))
+  }
+}
+
+// program_name(&AST) -> String
+fn program_name(tree: &AST) -> String {
+  // the program node is the tree, or one of its children when comments surround it
+  for _node in std::iter::once(tree).chain(tree.children.iter()) {
+    if let (Token::PROGRAM, Some(Token::ID(_string))) = (&_node.token, _node.children.first().map(|node| &node.token)) {
+      return names::canonical(_string);
+    }
+  }
+  return String::new();
+}
+
+// compile(&str, &Options) -> Result<CompiledModule, Diagnostics>
+pub fn compile(source: &str, options: &Options) -> Result<CompiledModule, Diagnostics> {
+  let mut parser = parser(source, options)?;
+  let tree = match parser.parse() {
+    Ok(_tree) => _tree,
+    Err(why) => return Err(parser.diagnostics(why))
+  };
+  let wat = emit(&tree, options)?;
+  return Ok(CompiledModule {
+    name: program_name(&tree),
+    tree: tree,
+    symbols: parser.symbol_table.clone(),
+    wat: wat,
+    switches: parser.preprocessor.switches.clone(),
+    files: parser.preprocessor.files.clone(),
+  });
+}
+
+// assemble(&str) -> Result<Vec<u8>, Diagnostics>
+#[cfg(feature = "wasm")]
+pub fn assemble(wat: &str) -> Result<Vec<u8>, Diagnostics> {
+  match wat::parse_str(wat) {
+    Ok(_wasm) => return Ok(_wasm),
+    Err(why) => return Err(Diagnostics::from(Diagnostic::new(format!("{} : {}", PANIC_ASSEMBLE, why))))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tokenize() {
+    let options = Options::default();
+    assert_eq!(
+      super::tokenize("x := 42;", &options),
+      Ok(vec![Token::ID("x".to_string()), Token::ASSIGN, Token::INTEGER(42), Token::SEMICOLON])
+    );
+    assert_eq!(super::tokenize("x := ?; y := ?;", &options).unwrap_err().diagnostics.len(), 2);
+    // comments nest only when asked to
+    assert!(super::tokenize("{ { } } x", &options).is_err());
+    let options = Options { nested_comments: true, ..Options::default() };
+    assert_eq!(super::tokenize("{ { } } x", &options), Ok(vec![Token::ID("x".to_string())]));
+  }
+
+  #[test]
+  fn tokenize_with_spans() {
+    assert_eq!(
+      super::tokenize_with_spans("x := 42;\ny := x;", &Options::default()),
+      Ok(vec![
+        (Span::new(0, 1, 1, 1), Token::ID("x".to_string())),
+        (Span::new(2, 4, 1, 3), Token::ASSIGN),
+        (Span::new(5, 7, 1, 6), Token::INTEGER(42)),
+        (Span::new(7, 8, 1, 8), Token::SEMICOLON),
+        (Span::new(9, 10, 2, 1), Token::ID("y".to_string())),
+        (Span::new(11, 13, 2, 3), Token::ASSIGN),
+        (Span::new(14, 15, 2, 6), Token::ID("x".to_string())),
+        (Span::new(15, 16, 2, 7), Token::SEMICOLON),
+      ])
+    );
+  }
+
+  #[test]
+  fn parse_expression() {
+    let options = Options::default();
+    let tree = super::parse_expression("1 + 2", &options).unwrap();
+    assert_eq!(tree.token, Token::PLUS);
+    assert_eq!(tree.children.len(), 2);
+    assert!(super::parse_expression("1 +", &options).is_err());
+  }
+
+  #[test]
+  fn compile() {
+    let options = Options { comments: false, ..Options::default() };
+    let module = super::compile("{ answer } program Answer; var x: real; begin x := 42 end.", &options).unwrap();
+    assert_eq!(module.name, "answer");
+    assert_eq!(module.wat, ";; this file is generated\n(module\n  (global $x (mut f64) (f64.const 0))\n  (func $answer (export \"answer\")\n    (global.set $x\n      (f64.const 42)\n    )\n  )\n)\n");
+    assert!(super::compile("x := 42;", &options).is_err());
+    // errors in an included file are reported with its name
+    let directory = std::env::temp_dir().join(format!("pas2wat-compile-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("body.inc"), "begin\n  x := ;\nend").unwrap();
+    let options = Options { unit_paths: vec![directory.to_string_lossy().to_string()], ..options };
+    let diagnostics = super::compile("program p; var x: real;\n{$I body.inc}.", &options).unwrap_err();
+    assert_eq!(diagnostics.diagnostics[0].span.map(|span| (span.line, span.column)), Some((2, 8)));
+    assert!(diagnostics.to_string().starts_with(&format!("{}:2:8 : ", directory.join("body.inc").to_string_lossy())));
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert!(super::compile("program p; begin x := ; end.", &options).is_err());
+  }
+
+  #[test]
+  #[cfg(feature = "wasm")]
+  fn assemble() {
+    assert_eq!(super::assemble("(module)").unwrap(), b"\0asm\x01\0\0\0".to_vec());
+    assert!(super::assemble("(local.set $x)").is_err());
+    let options = Options { comments: false, ..Options::default() };
+    let source = "program count; var i, n: real; begin n := 0; i := 0; while i < 5 do begin i := i + 1; if i <> 3 then n := n + i end end.";
+    assert!(super::assemble(&super::compile(source, &options).unwrap().wat).is_ok());
+    let source = "program sort; var a, b: integer; procedure Swap(var x, y: integer); var t: integer; begin t := x; x := y; y := t end; begin a := 2; b := 1; if a > b then Swap(a, b) end.";
+    assert!(super::assemble(&super::compile(source, &options).unwrap().wat).is_ok());
+    let source = "program numbers; function Fib(n: integer): integer; begin if n < 2 then Fib := n else Fib := Fib(n - 1) + Fib(n - 2) end; begin writeln(Fib(10)) end.";
+    assert!(super::assemble(&super::compile(source, &options).unwrap().wat).is_ok());
+    let options = Options { comments: false, target: crate::options::Target::WASI, ..Options::default() };
+    assert!(super::assemble(&super::compile(source, &options).unwrap().wat).is_ok());
+  }
+
+  #[test]
+  #[cfg(feature = "wasm")]
+  fn run() {
+    // programs are run with node and the glue, when node is installed
+    if std::process::Command::new("node").arg("--version").output().is_err() {
+      return;
+    }
+    let directory = std::env::temp_dir().join(format!("pas2wat-run-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let run = |depth: i32| {
+      let source = format!("program deep; var x, y: integer; procedure Add(var a: integer; b: integer); begin a := a + b end; function Sum(n: integer): integer; var s: integer; begin s := 0; if n > 0 then begin Add(s, n); s := s + Sum(n - 1) end; Sum := s end; begin x := 9; y := 7; Add(x, 0); Add(y, 0); writeln(Sum({})); writeln(x); writeln(y) end.", depth);
+      let module = super::compile(&source, &Options::default()).unwrap();
+      std::fs::write(directory.join("deep.wasm"), super::assemble(&module.wat).unwrap()).unwrap();
+      std::fs::write(directory.join("deep.js"), crate::emitter::glue(&module.name, crate::options::Target::BROWSER)).unwrap();
+      std::fs::write(directory.join("run.js"), "global.wasmModule = new WebAssembly.Module(require('fs').readFileSync(__dirname + '/deep.wasm'));\nrequire('./deep.js');\n").unwrap();
+      return std::process::Command::new("node").arg(directory.join("run.js")).output().unwrap();
+    };
+    let output = run(100);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "5050\n9\n7\n");
+    // recursion deeper than the shadow stack is a trap, not a write over the globals
+    let output = run(10000);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unreachable"));
+    std::fs::remove_dir_all(&directory).unwrap();
+  }
+}
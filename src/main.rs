@@ -1,112 +1,535 @@
-#![allow(dead_code)]
-#![allow(non_camel_case_types)]
-#![allow(unused_variables)]
-#![allow(unused_assignments)]
-
 use std::env;
-// use std::fs::{ self, File };
-// use std::io::{ BufReader, BufWriter, Write };
-// use std::io::prelude::*;
-
-mod ast;
-mod config;
-mod consts;
-mod emitter;
-mod evaluator;
-mod lexer;
-mod parser;
-
-// use crate::config::*;
-// use crate::consts::*;
-// use crate::emitter::Emitter;
-use crate::lexer::Lexer;
-use crate::parser::Parser;
-
-// only need this for testing
-mod token;
-// use crate::token::{ Token };
+use std::fs::{ self, File };
+use std::io::{ self, Read, Write };
+use std::path::Path;
+use std::process;
+use std::sync::Mutex;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::thread;
+use std::time::{ Duration, SystemTime };
+
+use pas2wat::consts::*;
+use pas2wat::diagnostic::{ Diagnostic, Diagnostics };
+use pas2wat::dump;
+use pas2wat::emitter;
+use pas2wat::options::{ Dialect, Options, Target };
+use pas2wat::project::Project;
+use pas2wat::repl::Repl;
+
+// path used for stdin and stdout
+const ARG_STDIO                     : &str = "-";
+const ARG_REPL                      : &str = "repl";
+const ARG_BUILD                     : &str = "build";
+const ARG_CONFIG                    : &str = "--config";
+const ARG_OUTPUT                    : &str = "-o";
+const ARG_OUT_DIR                   : &str = "--out-dir";
+const ARG_JOBS                      : &str = "--jobs";
+const ARG_DIALECT                   : &str = "--dialect";
+const ARG_DEFINE                    : &str = "--define";
+const ARG_WATCH                     : &str = "--watch";
+const ARG_EMIT                      : &str = "--emit";
+const ARG_WAT                       : &str = "--wat";
+const ARG_HELP                      : &str = "--help";
+const ARG_DUMP_CHARS                : &str = "--dump-chars";
+const ARG_DUMP_TOKENS               : &str = "--dump-tokens";
+const ARG_DUMP_AST                  : &str = "--dump-ast";
+const ARG_DUMP_SYMBOLS              : &str = "--dump-symbols";
+const ARG_NO_COMMENTS               : &str = "--no-comments";
+const ARG_NESTED_COMMENTS           : &str = "--nested-comments";
+const ARG_SOURCE_COMMENTS           : &str = "--source-comments";
+const USAGE                         : &str = "usage: pas2wat [<input>.pas | -] [-o <output> | -o -] [options]
+       pas2wat <input>.pas... --out-dir <directory> [options]
+       pas2wat build [--config <file>] [<input>.pas...] [options]
+       pas2wat repl [--wat] [options]
+
+options:
+  --config <file>   project file used by build (default is pas2wat.toml)
+  --out-dir <dir>   write output for every input to <dir>
+  --jobs <n>        number of programs compiled in parallel (default is number of cpus)
+  --watch           recompile inputs when they change
+  --dialect <name>  iso, turbo or delphi reserved words (default is turbo)
+  -d, --define <x>  define symbol x for {$IFDEF x} in the source
+  --emit=<stages>   comma separated list of tokens, ast, symbols, wat, wasm, js (default is wat)
+  --wat             show the WAT generated for each line in the repl
+  --dump-chars      show characters recognised by the lexer
+  --dump-tokens     show tokens consumed by the parser
+  --dump-ast        show the AST representation of the program
+  --dump-symbols    show the symbol table and assignment table
+  --no-comments     do not include compiler comments in generated code
+  --nested-comments allow nested { } and (* *) comments in the source
+  --source-comments include comments from the source in generated code";
+
+// name of entry point when reading stdin
+const DEFAULT_NAME                  : &str = "main";
+
+// interval between checking watched files
+const WATCH_INTERVAL                : Duration = Duration::from_millis(500);
+
+// exit codes
+const EXIT_FAILURE                  : i32 = 1;
+const EXIT_USAGE                    : i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stage {
+  TOKENS,
+  AST,
+  SYMBOLS,
+  WAT,
+  WASM,
+  JS,
+}
+
+impl Stage {
+  // parse(&str) -> Option<Stage>
+  fn parse(name: &str) -> Option<Stage> {
+    match name {
+      "tokens" => return Some(Stage::TOKENS),
+      "ast" => return Some(Stage::AST),
+      "symbols" => return Some(Stage::SYMBOLS),
+      "wat" => return Some(Stage::WAT),
+      "wasm" => return Some(Stage::WASM),
+      "js" => return Some(Stage::JS),
+      _ => return None
+    }
+  }
+  // extension() -> &str
+  fn extension(&self) -> &'static str {
+    match self {
+      Stage::TOKENS => return ".tokens",
+      Stage::AST => return ".ast",
+      Stage::SYMBOLS => return ".symbols",
+      Stage::WAT => return WASM_WAT,
+      Stage::WASM => return WASM_BINARY,
+      Stage::JS => return WASM_JS,
+    }
+  }
+}
+
+struct Arguments {
+  inputs: Vec<String>,
+  output: Option<String>,
+  out_dir: Option<String>,
+  jobs: usize,
+  watch: bool,
+  repl: bool,
+  wat: bool,
+  build: bool,
+  config: Option<String>,
+  stages: Vec<Stage>,
+  dialect: Option<Dialect>,
+  options: Options,
+}
+
+// parse_stages(&str) -> Result<Vec<Stage>, String>
+fn parse_stages(list: &str) -> Result<Vec<Stage>, String> {
+  let mut stages = vec![];
+  for _name in list.split(',') {
+    match Stage::parse(_name.trim()) {
+      Some(_stage) => stages.push(_stage),
+      None => return Err(format!("{} : unknown stage", _name))
+    }
+  }
+  return Ok(stages);
+}
+
+// parse_arguments(Vec<String>) -> Result<Arguments, String>
+fn parse_arguments(args: &[String]) -> Result<Arguments, String> {
+  let mut arguments = Arguments {
+    inputs: vec![],
+    output: None,
+    out_dir: None,
+    jobs: thread::available_parallelism().map(|jobs| jobs.get()).unwrap_or(1),
+    watch: false,
+    repl: false,
+    wat: false,
+    build: false,
+    config: None,
+    stages: vec![],
+    dialect: None,
+    options: Options::default(),
+  };
+  let mut args = args.iter().skip(1);
+  while let Some(_arg) = args.next() {
+    match _arg.as_str() {
+      ARG_HELP | "-h" => {
+        println!("{}", USAGE);
+        process::exit(0);
+      },
+      ARG_DUMP_CHARS => arguments.options.dump_chars = true,
+      ARG_DUMP_TOKENS => arguments.options.dump_tokens = true,
+      ARG_DUMP_AST => arguments.options.dump_ast = true,
+      ARG_DUMP_SYMBOLS => arguments.options.dump_symbols = true,
+      ARG_NO_COMMENTS => arguments.options.comments = false,
+      ARG_NESTED_COMMENTS => arguments.options.nested_comments = true,
+      ARG_SOURCE_COMMENTS => arguments.options.trivia = true,
+      ARG_WATCH => arguments.watch = true,
+      ARG_WAT => arguments.wat = true,
+      ARG_REPL if arguments.inputs.is_empty() && !arguments.repl && !arguments.build => arguments.repl = true,
+      ARG_BUILD if arguments.inputs.is_empty() && !arguments.repl && !arguments.build => arguments.build = true,
+      ARG_CONFIG => {
+        match args.next() {
+          Some(_config) => arguments.config = Some(_config.clone()),
+          None => return Err(format!("{} : missing project file", ARG_CONFIG))
+        }
+      },
+      ARG_OUTPUT => {
+        match args.next() {
+          Some(_output) => arguments.output = Some(_output.clone()),
+          None => return Err(format!("{} : missing output file", ARG_OUTPUT))
+        }
+      },
+      ARG_OUT_DIR => {
+        match args.next() {
+          Some(_out_dir) => arguments.out_dir = Some(_out_dir.clone()),
+          None => return Err(format!("{} : missing output directory", ARG_OUT_DIR))
+        }
+      },
+      ARG_DIALECT => {
+        match args.next().map(|name| Dialect::parse(name)) {
+          Some(Some(_dialect)) => arguments.dialect = Some(_dialect),
+          _ => return Err(format!("{} : expected iso, turbo or delphi", ARG_DIALECT))
+        }
+      },
+      ARG_DEFINE | "-d" => {
+        match args.next() {
+          Some(_define) => arguments.options.defines.push(_define.clone()),
+          None => return Err(format!("{} : missing symbol", ARG_DEFINE))
+        }
+      },
+      ARG_JOBS | "-j" => {
+        match args.next().map(|jobs| jobs.parse::<usize>()) {
+          Some(Ok(_jobs)) if _jobs > 0 => arguments.jobs = _jobs,
+          _ => return Err(format!("{} : expected number of jobs", ARG_JOBS))
+        }
+      },
+      ARG_EMIT => {
+        match args.next() {
+          Some(_list) => arguments.stages.extend(parse_stages(_list)?),
+          None => return Err(format!("{} : missing stages", ARG_EMIT))
+        }
+      },
+      _ if _arg.starts_with(&format!("{}=", ARG_EMIT)) => {
+        arguments.stages.extend(parse_stages(&_arg[ARG_EMIT.len() + 1..])?);
+      },
+      _ if _arg.starts_with("--") => return Err(format!("{} : unknown option", _arg)),
+      _ => arguments.inputs.push(_arg.clone()),
+    }
+  }
+  if arguments.config.is_some() && !arguments.build {
+    return Err(format!("{} : can only be used with {}", ARG_CONFIG, ARG_BUILD));
+  }
+  if arguments.repl && !arguments.inputs.is_empty() {
+    return Err(format!("{} : does not take input files", ARG_REPL));
+  }
+  if arguments.inputs.len() > 1 && arguments.output.is_some() {
+    return Err(format!("{} : cannot be used with several inputs, use {}", ARG_OUTPUT, ARG_OUT_DIR));
+  }
+  // a build takes its stages from the project file
+  if arguments.stages.is_empty() && !arguments.build {
+    arguments.stages.push(Stage::WAT);
+  }
+  let mut stages = vec![];
+  for _stage in &arguments.stages {
+    if !stages.contains(_stage) {
+      stages.push(*_stage);
+    }
+  }
+  arguments.stages = stages;
+  if let Some(_dialect) = arguments.dialect {
+    arguments.options.dialect = _dialect;
+  }
+  return Ok(arguments);
+}
+
+// configure(Arguments) -> Result<(), String>
+fn configure(arguments: &mut Arguments) -> Result<(), String> {
+  // flags given with build take precedence over the project file
+  let project = Project::load(arguments.config.as_deref().unwrap_or(PROJECT_FILE)).map_err(|why| why.to_string())?;
+  if arguments.inputs.is_empty() {
+    arguments.inputs = project.programs;
+  }
+  if arguments.out_dir.is_none() {
+    arguments.out_dir = Some(project.out_dir);
+  }
+  if arguments.stages.is_empty() {
+    for _stage in parse_stages(&project.emit.join(","))? {
+      if !arguments.stages.contains(&_stage) {
+        arguments.stages.push(_stage);
+      }
+    }
+  }
+  if project.options.target == Target::BROWSER && !arguments.stages.contains(&Stage::JS) {
+    arguments.stages.push(Stage::JS);
+  }
+  arguments.options.comments = arguments.options.comments && project.options.comments;
+  arguments.options.nested_comments = arguments.options.nested_comments || project.options.nested_comments;
+  arguments.options.trivia = arguments.options.trivia || project.options.trivia;
+  arguments.options.target = project.options.target;
+  arguments.options.dialect = arguments.dialect.unwrap_or(project.options.dialect);
+  arguments.options.unit_paths = project.options.unit_paths;
+  arguments.options.defines.extend(project.options.defines);
+  return Ok(());
+}
+
+// read_source(Option<&str>) -> Result<String, Diagnostic>
+fn read_source(input: Option<&str>) -> Result<String, Diagnostic> {
+  let mut source = String::new();
+  let result = match input {
+    None | Some(ARG_STDIO) => io::stdin().read_to_string(&mut source),
+    Some(_path) => File::open(_path).and_then(|mut file| file.read_to_string(&mut source)),
+  };
+  match result {
+    Ok(_) => return Ok(source),
+    Err(why) => return Err(Diagnostic::new(format!("{} : {}", PANIC_READ, why)))
+  }
+}
+
+// write_target(&str, &[u8]) -> Result<(), Diagnostic>
+fn write_target(target: &str, bytes: &[u8]) -> Result<(), Diagnostic> {
+  let result = if target == ARG_STDIO {
+    io::stdout().write_all(bytes)
+  } else {
+    File::create(target).and_then(|mut file| file.write_all(bytes))
+  };
+  match result {
+    Ok(_) => return Ok(()),
+    Err(why) => return Err(Diagnostic::new(format!("{} : {} : {}", PANIC_WRITE, target, why)))
+  }
+}
+
+// target_path(Arguments, Option<&str>, Stage) -> String
+fn target_path(arguments: &Arguments, input: Option<&str>, stage: Stage) -> String {
+  // a single stage is written to -o as given, otherwise each stage gets its own extension
+  match (arguments.out_dir.as_deref(), arguments.output.as_deref(), input) {
+    (Some(_out_dir), _, _) => return Path::new(_out_dir).join(module_name(input)).with_extension(&stage.extension()[1..]).to_string_lossy().to_string(),
+    (None, Some(ARG_STDIO), _) => return ARG_STDIO.to_string(),
+    (None, Some(_output), _) if arguments.stages.len() == 1 => return _output.to_string(),
+    (None, Some(_output), _) => return Path::new(_output).with_extension(&stage.extension()[1..]).to_string_lossy().to_string(),
+    (None, None, None) | (None, None, Some(ARG_STDIO)) => return ARG_STDIO.to_string(),
+    (None, None, Some(_input)) => return Path::new(_input).with_extension(&stage.extension()[1..]).to_string_lossy().to_string(),
+  }
+}
+
+// module_name(Option<&str>) -> String
+fn module_name(input: Option<&str>) -> String {
+  match input.map(Path::new).and_then(Path::file_stem) {
+    Some(_stem) if input != Some(ARG_STDIO) => return _stem.to_string_lossy().to_string(),
+    _ => return DEFAULT_NAME.to_string(),
+  }
+}
+
+// collision(Arguments) -> Option<(String, String)>
+fn collision(arguments: &Arguments) -> Option<(String, String)> {
+  // outputs in --out-dir are named by file stem, so two inputs with the same stem would overwrite each other
+  arguments.out_dir.as_ref()?;
+  for (_index, _input) in arguments.inputs.iter().enumerate() {
+    let name = module_name(Some(_input));
+    if let Some(_other) = arguments.inputs[.._index].iter().find(|input| module_name(Some(input)) == name) {
+      return Some((_other.clone(), _input.clone()));
+    }
+  }
+  return None;
+}
+
+// out_dir(Arguments) -> bool
+fn out_dir(arguments: &Arguments) -> bool {
+  if let Some((_first, _second)) = collision(arguments) {
+    eprintln!("error: {} : {} and {}", PANIC_OUTPUT, _first, _second);
+    return false;
+  }
+  if let Some(_out_dir) = &arguments.out_dir {
+    if let Err(why) = fs::create_dir_all(_out_dir) {
+      eprintln!("error: {} : {} : {}", PANIC_WRITE, _out_dir, why);
+      return false;
+    }
+  }
+  return true;
+}
+
+// assemble(&str) -> Result<Vec<u8>, Diagnostics>
+#[cfg(feature = "wasm")]
+fn assemble(wat: &str) -> Result<Vec<u8>, Diagnostics> {
+  return pas2wat::assemble(wat);
+}
+
+// assemble(&str) -> Result<Vec<u8>, Diagnostics>
+#[cfg(not(feature = "wasm"))]
+fn assemble(_wat: &str) -> Result<Vec<u8>, Diagnostics> {
+  return Err(Diagnostics::from(Diagnostic::new(format!("{} : compiled without the wasm feature", PANIC_ASSEMBLE))));
+}
+
+// run(Arguments, Option<&str>) -> Result<Vec<String>, Diagnostics>
+fn run(arguments: &Arguments, input: Option<&str>) -> Result<Vec<String>, Diagnostics> {
+  let source = read_source(input)?;
+  // include files are searched next to the input first
+  let mut options = arguments.options.clone();
+  if let Some(_directory) = input.filter(|input| *input != ARG_STDIO).and_then(|input| Path::new(input).parent()) {
+    options.unit_paths.insert(0, _directory.to_string_lossy().to_string());
+  }
+  // every stage is built before anything is written
+  let module = pas2wat::compile(&source, &options)?;
+  let mut artifacts = vec![];
+  for _stage in &arguments.stages {
+    let bytes = match _stage {
+      Stage::TOKENS => dump::tokens(&pas2wat::tokenize_with_spans(&source, &options)?).into_bytes(),
+      Stage::AST => dump::tree(&module.tree).into_bytes(),
+      Stage::SYMBOLS => dump::symbols(&module.symbols).into_bytes(),
+      Stage::WAT => module.wat.clone().into_bytes(),
+      Stage::WASM => assemble(&module.wat)?,
+      Stage::JS => emitter::glue(&module.name, options.target).into_bytes(),
+    };
+    artifacts.push((target_path(arguments, input, *_stage), bytes));
+  }
+  for (_target, _bytes) in artifacts {
+    write_target(&_target, &_bytes)?;
+  }
+  // files the input depends on, for watch
+  return Ok(module.files);
+}
+
+// run_all(Arguments) -> Vec<Result<Vec<String>, Diagnostics>>
+fn run_all(arguments: &Arguments) -> Vec<Result<Vec<String>, Diagnostics>> {
+  // each thread takes the next input until all are compiled
+  let next = AtomicUsize::new(0);
+  let results = Mutex::new(vec![Ok(vec![]); arguments.inputs.len()]);
+  thread::scope(|scope| {
+    for _ in 0..arguments.jobs.min(arguments.inputs.len()) {
+      scope.spawn(|| {
+        loop {
+          let index = next.fetch_add(1, Ordering::SeqCst);
+          if index >= arguments.inputs.len() {
+            break;
+          }
+          let result = run(arguments, Some(&arguments.inputs[index]));
+          results.lock().unwrap()[index] = result;
+        }
+      });
+    }
+  });
+  return results.into_inner().unwrap();
+}
+
+// batch(Arguments) -> bool
+fn batch(arguments: &Arguments) -> bool {
+  if !out_dir(arguments) {
+    return false;
+  }
+  let results = run_all(arguments);
+  let mut failures = 0;
+  for (_input, _result) in arguments.inputs.iter().zip(&results) {
+    match _result {
+      Ok(_) => report(_input, _result),
+      Err(_diagnostics) => {
+        failures += 1;
+        match _diagnostics.diagnostics.first() {
+          Some(_diagnostic) => eprintln!("failed  {} : {}", _input, _diagnostic),
+          None => eprintln!("failed  {}", _input),
+        }
+      }
+    }
+  }
+  eprintln!("{} compiled, {} failed", results.len() - failures, failures);
+  return failures == 0;
+}
+
+// report(&str, Result<Vec<String>, Diagnostics>)
+fn report(input: &str, result: &Result<Vec<String>, Diagnostics>) {
+  match result {
+    Ok(_) => eprintln!("ok      {}", input),
+    Err(_diagnostics) => {
+      for _diagnostic in &_diagnostics.diagnostics {
+        eprintln!("error: {} : {}", input, _diagnostic);
+      }
+    }
+  }
+}
+
+// modified(&str) -> Option<SystemTime>
+fn modified(path: &str) -> Option<SystemTime> {
+  return fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+}
+
+// watch(Arguments) -> !
+fn watch(arguments: &Arguments) -> ! {
+  if !out_dir(arguments) {
+    process::exit(EXIT_FAILURE);
+  }
+  // files each input depends on, with the time they were last compiled
+  let mut watched: Vec<Vec<(String, Option<SystemTime>)>> = arguments.inputs.iter().map(|input| vec![(input.clone(), None)]).collect();
+  eprintln!("watching {} file(s), press ctrl-c to stop", arguments.inputs.len());
+  loop {
+    for (_input, _files) in arguments.inputs.iter().zip(watched.iter_mut()) {
+      let mut changed = false;
+      for (_path, _time) in _files.iter_mut() {
+        let time = modified(_path);
+        if time != *_time {
+          *_time = time;
+          changed = true;
+        }
+      }
+      if changed {
+        let result = run(arguments, Some(_input));
+        report(_input, &result);
+        // include files are watched from the first time they are read
+        if let Ok(_includes) = &result {
+          for _include in _includes {
+            if !_files.iter().any(|(_path, _)| _path == _include) {
+              _files.push((_include.clone(), modified(_include)));
+            }
+          }
+        }
+      }
+    }
+    thread::sleep(WATCH_INTERVAL);
+  }
+}
 
 fn main() {
   let args: Vec<String> = env::args().collect();
-  // println!("{:?}", args);
-
-  let lexer = Lexer::new("
-    test := 42;
-  ".to_string());
-  // let mut token = lexer.get_next_token();
-  // while token != Token::EOF {
-  //   println!("{}{}{:?}", FORMAT_TAB, FORMAT_SPACE.repeat(2), token);
-  //   token = lexer.get_next_token();
-  // };
-  let tree = Parser::new(lexer).parse();
-
-  println!("{:?}", tree);
-  // AST { token: ASSIGN, children: [AST { token: ID("test"), children: [] }, AST { token: INTEGER(42), children: [] }] }
-
-  // if DEBUG && DEBUG_WITH_INPUT {
-  //   let mut source_file = String::new();
-
-  //   // println!("{:?}", args);
-
-  //   if args.len() as i32 > 1 {
-  //     source_file = String::from(&args[1]);
-  //     // file_name
-  //     let input_string = String::from(&source_file);
-  //     let file_name: Vec<&str> = input_string.split('.').collect();
-  //     // read input file
-  //     let mut input = String::new();
-  //     match BufReader::new(File::open(source_file).expect(PANIC_READ)).read_to_string(&mut input) {
-  //       Err(why) => panic!("{} : {}", PANIC_READ, why),
-  //       Ok(_) => {
-  //         // target file (optional)
-  //         let mut target_file = String::new();
-  //         if args.len() as i32 > 2 {
-  //             target_file = String::from(&args[2]);
-  //         } else {
-  //             target_file = format!("{}{}", file_name[0].to_string(), WASM_WAT);
-  //         }
-  //         let target_js = format!("{}{}", file_name[0].to_string(), WASM_JS);
-  //         // lexer
-  //         let lexer = Lexer::new(input.to_string());
-  //         // parser
-  //         let parser = Parser::new(lexer);
-  //         // emitter
-  //         let file = File::create(target_file).expect(PANIC_WRITE);
-  //         let file = BufWriter::new(file);
-  //         let mut emitter = Emitter::new(parser, file);
-  //         // compile
-  //         emitter.compile();
-  //         // js
-  //         let js = File::create(target_js).expect(PANIC_WRITE);
-  //         let mut js = BufWriter::new(js);
-  //         js.write_all(format!("/* this file is generated */{}const wasmInstance = new WebAssembly.Instance(wasmModule, {{console}});{}const {{ {} }} = wasmInstance.exports;{}{}();", FORMAT_NEWLINE, FORMAT_NEWLINE, file_name[0].to_string(), FORMAT_NEWLINE, file_name[0].to_string()).as_bytes()).expect(PANIC_WRITE);
-  //       },
-  //     }
-  //     // show result
-  //     if DEBUG && DEBUG_SHOW_RESULT {
-  //       let result = fs::read_to_string(format!("{}{}", file_name[0].to_string(), WASM_WAT)).expect(PANIC_READ);
-  //       println!("{}{}START RESULT{}{}", "-".repeat(14), FORMAT_SPACE, FORMAT_SPACE, "-".repeat(14));
-  //       for line in result.lines() {
-  //         println!("{}", line);
-  //       }
-  //       println!("{}{}END RESULT{}{}", "-".repeat(15), FORMAT_SPACE, FORMAT_SPACE, "-".repeat(15));
-  //     }
-  //   } else {
-  //     panic!("{} : {}", PANIC_READ, PANIC_FILE);
-  //   }
-  // // run parts of compiler using custom input
-  // } else {
-  //   let lexer = Lexer::new("
-  //     test := 42;
-  //   ".to_string());
-  //   // let mut token = lexer.get_next_token();
-  //   // while token != Token::EOF {
-  //   //   println!("{}{}{:?}", FORMAT_TAB, FORMAT_SPACE.repeat(2), token);
-  //   //   token = lexer.get_next_token();
-  //   // };
-  //   let tree = Parser::new(lexer, DEBUG_SHOW_TOKEN).parse();
-
-  //   println!("{:?}", tree);
-  //   // AST { token: ASSIGN, children: [AST { token: ID("test"), children: [] }, AST { token: INTEGER(42), children: [] }] }
-  // }
+
+  let mut arguments = match parse_arguments(&args) {
+    Ok(_arguments) => _arguments,
+    Err(why) => {
+      eprintln!("error: {}", why);
+      eprintln!("{}", USAGE);
+      process::exit(EXIT_USAGE);
+    }
+  };
+  if arguments.build {
+    if let Err(why) = configure(&mut arguments) {
+      eprintln!("error: {}", why);
+      process::exit(EXIT_FAILURE);
+    }
+  }
+  // the inputs of a build are only known once the project file is read
+  if arguments.watch && (arguments.inputs.is_empty() || arguments.inputs.iter().any(|input| input == ARG_STDIO)) {
+    eprintln!("error: {} : cannot watch stdin", ARG_WATCH);
+    eprintln!("{}", USAGE);
+    process::exit(EXIT_USAGE);
+  }
+  if arguments.repl {
+    let mut repl = Repl::new(&arguments.options);
+    repl.show_wat = arguments.wat;
+    if let Err(why) = repl.run(io::stdin().lock(), &mut io::stdout()) {
+      eprintln!("error: {}", why);
+      process::exit(EXIT_FAILURE);
+    }
+    return;
+  }
+  if arguments.watch {
+    watch(&arguments);
+  }
+  // several inputs, or an output directory, is a batch
+  if arguments.inputs.len() > 1 || arguments.out_dir.is_some() {
+    if !batch(&arguments) {
+      process::exit(EXIT_FAILURE);
+    }
+    return;
+  }
+  if let Err(diagnostics) = run(&arguments, arguments.inputs.first().map(|input| input.as_str())) {
+    for _diagnostic in &diagnostics.diagnostics {
+      eprintln!("error: {}", _diagnostic);
+    }
+    process::exit(EXIT_FAILURE);
+  }
 }
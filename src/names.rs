@@ -0,0 +1,89 @@
+use crate::token::Token;
+
+// separates the scopes in a mangled name, never part of a Pascal identifier
+const CHAR_SCOPE                    : char = '/';
+// starts an escaped character in a mangled name, never part of a Pascal identifier
+const CHAR_ESCAPE                   : char = '.';
+// starts a name added by the compiler, never part of a Pascal identifier
+const CHAR_INTERNAL                 : char = '!';
+
+/*
+
+  Pascal identifiers are not case sensitive, so Fn and fn are the same variable. Tokens keep the
+  spelling from the source and names are compared in canonical form.
+
+  WAT identifiers are $ followed by ASCII letters, digits and some punctuation. A mangled name is
+  the canonical scopes and name separated by /, with any other character escaped as . and six hex
+  digits, so smörgås in procedure Eat is $eat/sm.0000f6rg.0000e5s. Pascal identifiers never contain
+  / or ., which makes every mangled name unique. Names the compiler adds, such as the stack pointer,
+  start with ! so they are never the same as a mangled name.
+
+*/
+
+// canonical(&str) -> String
+pub fn canonical(name: &str) -> String {
+  return name.to_lowercase();
+}
+
+// same(&Token, &Token) -> bool
+pub fn same(first: &Token, second: &Token) -> bool {
+  match (first, second) {
+    (Token::ID(_first), Token::ID(_second)) => return canonical(_first) == canonical(_second),
+    _ => return first == second
+  }
+}
+
+// escape(&str) -> String
+fn escape(name: &str) -> String {
+  let mut escaped = String::new();
+  for _char in canonical(name).chars() {
+    if _char.is_ascii_alphanumeric() || _char == '_' {
+      escaped.push(_char);
+    } else {
+      escaped.push_str(&format!("{}{:06x}", CHAR_ESCAPE, _char as u32));
+    }
+  }
+  return escaped;
+}
+
+// mangle(&[String], &str) -> String
+pub fn mangle(scopes: &[String], name: &str) -> String {
+  let mut mangled = String::from("$");
+  for _scope in scopes {
+    mangled.push_str(&escape(_scope));
+    mangled.push(CHAR_SCOPE);
+  }
+  mangled.push_str(&escape(name));
+  return mangled;
+}
+
+// internal(&[String], &str) -> String
+pub fn internal(scopes: &[String], name: &str) -> String {
+  return format!("{}{}{}", mangle(scopes, ""), CHAR_INTERNAL, name);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn same() {
+    assert!(super::same(&Token::ID("Fn".to_string()), &Token::ID("fN".to_string())));
+    assert!(!super::same(&Token::ID("x".to_string()), &Token::ID("y".to_string())));
+    assert!(super::same(&Token::PLUS, &Token::PLUS));
+  }
+
+  #[test]
+  fn mangle() {
+    assert_eq!(super::mangle(&[], "Fn"), "$fn");
+    assert_eq!(super::mangle(&["Eat".to_string()], "smörgås"), "$eat/sm.0000f6rg.0000e5s");
+    assert_eq!(internal(&[], "sp"), "$!sp");
+    assert_eq!(internal(&["Swap".to_string()], "frame"), "$swap/!frame");
+    // names that look alike are still different
+    let names = [super::mangle(&["a".to_string()], "b"), super::mangle(&[], "a_b"), super::mangle(&[], "ab"), super::mangle(&[], "aö"), super::mangle(&[], "a_0000f6")];
+    for (_index, _name) in names.iter().enumerate() {
+      assert!(!names[_index + 1..].contains(_name));
+      assert!(_name[1..].chars().all(|_char| _char.is_ascii_alphanumeric() || "_./#".contains(_char)));
+    }
+  }
+}
@@ -0,0 +1,88 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+  // module with JavaScript glue to instantiate it in a browser
+  BROWSER,
+  // module with the entry point exported as _start
+  WASI,
+  // module only
+  BARE,
+}
+
+impl Target {
+  // parse(&str) -> Option<Target>
+  pub fn parse(name: &str) -> Option<Target> {
+    match name {
+      "browser" => return Some(Target::BROWSER),
+      "wasi" => return Some(Target::WASI),
+      "bare" => return Some(Target::BARE),
+      _ => return None
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dialect {
+  // ISO 7185 standard Pascal
+  ISO,
+  // Turbo Pascal
+  TURBO,
+  // Delphi and Free Pascal in delphi mode
+  DELPHI,
+}
+
+impl Dialect {
+  // parse(&str) -> Option<Dialect>
+  pub fn parse(name: &str) -> Option<Dialect> {
+    match name {
+      "iso" => return Some(Dialect::ISO),
+      "turbo" => return Some(Dialect::TURBO),
+      "delphi" => return Some(Dialect::DELPHI),
+      _ => return None
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+  // show characters recognised by the lexer
+  pub dump_chars: bool,
+  // show tokens consumed by the parser
+  pub dump_tokens: bool,
+  // show the AST representation of the program
+  pub dump_ast: bool,
+  // show the symbol table and assignment table
+  pub dump_symbols: bool,
+  // include compiler comments in generated code
+  pub comments: bool,
+  // allow comments of the same kind inside comments, as in Free Pascal
+  pub nested_comments: bool,
+  // keep whitespace and comments with tokens, and pass Pascal comments through to generated code
+  pub trivia: bool,
+  // environment the module is generated for
+  pub target: Target,
+  // Pascal dialect of the source
+  pub dialect: Dialect,
+  // directories searched for units and include files
+  pub unit_paths: Vec<String>,
+  // symbols defined before the source is read, as with {$DEFINE}
+  pub defines: Vec<String>,
+}
+
+impl Default for Options {
+  // default() -> Options
+  fn default() -> Options {
+    Options {
+      dump_chars: false,
+      dump_tokens: false,
+      dump_ast: false,
+      dump_symbols: false,
+      comments: true,
+      nested_comments: false,
+      trivia: false,
+      target: Target::BROWSER,
+      dialect: Dialect::TURBO,
+      unit_paths: vec![],
+      defines: vec![],
+    }
+  }
+}
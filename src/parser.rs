@@ -1,26 +1,35 @@
-use crate::config::*;
+use std::convert::TryFrom;
+
 use crate::consts::*;
-use crate::token::{ Type, Token };
+use crate::token::{ Type, Token, Span, Trivia };
 use crate::lexer::Lexer;
+use crate::preprocessor::Preprocessor;
 use crate::ast::AST;
-// use crate::evaluator::Evaluator;
+use crate::diagnostic::{ Diagnostic, Diagnostics };
+use crate::options::Options;
+use crate::names;
+use crate::evaluator::Evaluator;
 
 /*
 
-  program                 : PROGRAM variable SEMICOLON block DOT
+  program                 : PROGRAM variable (LPAR variable (COMMA variable)* RPAR)? SEMICOLON block DOT
 
   block                   : declarations compound_statement
 
-  declarations            : CONST (constant_declaration SEMICOLON)+ | VAR (variable_declaration SEMICOLON)+ | empty
+  declarations            : (CONST (constant_declaration SEMICOLON)+ | VAR (variable_declaration SEMICOLON)+ | procedure_declaration | function_declaration)*
 
-  constant_declaration    : ID EQUAL (INTEGER | REAL)
-  variable_declaration    : ID (COMMA ID)* COLON type_spec
+  constant_declaration    : ID EQUAL constant_expression
+  variable_declaration    : ID (COMMA ID)* COLON (type_spec | structured_type)
+  procedure_declaration   : PROCEDURE ID formal_parameters? SEMICOLON block SEMICOLON
+  function_declaration    : FUNCTION ID formal_parameters? COLON type_spec SEMICOLON block SEMICOLON
+  formal_parameters       : LPAR (parameter_group (SEMICOLON parameter_group)*)? RPAR
+  parameter_group         : (VAR | CONST | OUT)? ID (COMMA ID)* COLON type_spec
 
   compound_statement      : BEGIN statement (SEMICOLON statement)* END
-  statement               : compound_statement | structured_statement | assignment_statement | function_statement | empty
+  statement               : compound_statement | structured_statement | assignment_statement | call_statement | empty
   structured_statement    : if_statement | while_statement
-  assignment_statement    : variable ASSIGN (simple_expression | (TRUE | FALSE))
-  function_statement      : function LPAR simple_expression RPAR
+  assignment_statement    : variable ASSIGN expression
+  call_statement          : ID arguments?
   
   if_statement            : IF condition THEN statement (ELSE statement)?
   while_statement         : WHILE condition DO statement
@@ -29,11 +38,13 @@ use crate::ast::AST;
   simple_expression       : term ((PLUS | MINUS) term)*
 
   term                    : factor ((MULTIPLY | DIVIDE | INTEGER_DIV | INTEGER_MOD) factor)*
-  factor                  : PLUS factor | MINUS factor | INTEGER | REAL | LPAR simple_expression RPAR | variable
+  factor                  : PLUS factor | MINUS factor | INTEGER | REAL | TRUE | FALSE | LPAR simple_expression RPAR | ID arguments | variable
+  arguments               : LPAR (expression (COMMA expression)*)? RPAR
 
-  type_spec               : INTEGER | REAL
-  variable                : ID
-  function                : WRITELN | [TODO: add more built-in functions]
+  type_spec               : INTEGER | LONGINT | SMALLINT | REAL | BOOLEAN
+  structured_type         : (PACKED)? ARRAY LBRA constant_expression RANGE constant_expression RBRA OF type_spec
+  constant_expression     : expression
+  variable                : ID (LBRA constant_expression RBRA)?
   empty                   : 
 
 */
@@ -62,520 +73,706 @@ use crate::ast::AST;
 */
 
 pub struct Parser {
-  lexer: Lexer,
-  pub current_token: Option<Token>,
+  // tokens of the source after directives are applied
+  pub preprocessor: Preprocessor,
+  pub current_token: Token,
+  // where current_token is in the source
+  pub current_span: Span,
+  // whitespace and comments before current_token, kept with the trivia option
+  current_trivia: Vec<Trivia>,
   pub symbol_table: Vec<(Token, Type)>,
   pub assign_table: Vec<(Token, AST)>,
+  // declared constants and their values, put in place of the name where it is used
+  pub constant_table: Vec<(Token, AST)>,
+  // names declared in each scope, innermost last, since a name is declared once in a scope
+  declared: Vec<Vec<Token>>,
+  options: Options,
 }
 
 impl Parser {
-  // new(Lexer) -> Parser
-  pub fn new(lexer: Lexer) -> Parser {
+  // new(Lexer) -> Result<Parser, Diagnostic>
+  pub fn new(lexer: Lexer) -> Result<Parser, Diagnostic> {
+    return Parser::with_options(lexer, &Options::default());
+  }
+  // with_options(Lexer, Options) -> Result<Parser, Diagnostic>
+  pub fn with_options(lexer: Lexer, options: &Options) -> Result<Parser, Diagnostic> {
     let mut parser = Parser {
-      lexer: lexer,
-      current_token: None,
+      preprocessor: Preprocessor::new(lexer, options),
+      current_token: Token::EOF,
+      current_span: Span::default(),
+      current_trivia: vec![],
       symbol_table: vec![],
       assign_table: vec![],
+      constant_table: vec![],
+      declared: vec![vec![]],
+      options: options.clone(),
     };
-    parser.current_token = Some(parser.lexer.get_next_token());
-    return parser
+    parser.current_token = parser.preprocessor.get_next_token();
+    parser.current_span = parser.preprocessor.span();
+    parser.current_trivia = parser.preprocessor.trivia().to_vec();
+    return Ok(parser);
   }
   // eat
-  fn eat(&mut self, token: Token) {
-    if DEBUG && DEBUG_SHOW_TOKEN { println!("{}{}{:?}", FORMAT_TAB, FORMAT_SPACE.repeat(2), token); }
-    // clone used to deep copy value
-    if token == self.current_token.clone().unwrap() {
-      self.current_token = Some(self.lexer.get_next_token());
+  fn eat(&mut self, token: Token) -> Result<(), Diagnostic> {
+    if self.options.dump_tokens { eprintln!("{}{}{:?}", FORMAT_TAB, FORMAT_SPACE.repeat(2), token); }
+    if token == self.current_token {
+      self.current_token = self.preprocessor.get_next_token();
+      self.current_span = self.preprocessor.span();
+      self.current_trivia = self.preprocessor.trivia().to_vec();
     } else {
-      panic!("{:?} : {}", token, PANIC_SYNTAX)
+      return Err(Diagnostic::with_span(format!("{:?} : {}", token, PANIC_SYNTAX), self.current_span))
+    }
+    return Ok(());
+  }
+  // comments() -> Vec<AST>
+  fn comments(&mut self) -> Vec<AST> {
+    // comments before current_token, taken once so they are not repeated
+    let mut comments = vec![];
+    for _trivia in std::mem::take(&mut self.current_trivia) {
+      if let Trivia::COMMENT(_text) = _trivia {
+        comments.push(AST::new(Token::COMMENT(_text), vec![]));
+      }
     }
+    return comments;
+  }
+  // peek(usize) -> Token
+  fn peek(&mut self, offset: usize) -> Token {
+    // peek(1) is the token after current_token
+    return self.preprocessor.peek(offset - 1);
   }
   // type_spec() -> AST
-  // fn type_spec(&mut self) -> AST {
-  //   /*
-  //     type_spec : INTEGER | REAL
-  //   */
-  //   let token = self.current_token.clone().unwrap();
-  //   match token {
-  //     Token::TYPE_SPEC(Type::INTEGER) => {
-  //       // INTEGER
-  //       self.eat(Token::TYPE_SPEC(Type::INTEGER));
-  //       return AST::new(token, vec![]);
-  //     },
-  //     Token::TYPE_SPEC(Type::REAL) => {
-  //       // REAL
-  //       self.eat(Token::TYPE_SPEC(Type::REAL));
-  //       return AST::new(token, vec![]);
-  //     },
-  //     _ => panic!("{:?} : {}", token, PANIC_TYPE_DECLARATION)
-  //   }
-  // }
+  fn type_spec(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      type_spec : INTEGER | LONGINT | SMALLINT | REAL | BOOLEAN
+    */
+    // type names are identifiers, not reserved words, and integer types are all i32
+    let token = self.current_token.clone();
+    let _type = match &token {
+      Token::ID(_string) => match names::canonical(_string).as_str() {
+        "integer" | "longint" | "smallint" => Type::INTEGER,
+        "real" => Type::REAL,
+        "boolean" => Type::BOOLEAN,
+        _ => return Err(Diagnostic::with_span(format!("{:?} : {}", token, PANIC_TYPE_DECLARATION), self.current_span))
+      },
+      _ => return Err(Diagnostic::with_span(format!("{:?} : {}", token, PANIC_TYPE_DECLARATION), self.current_span))
+    };
+    self.eat(token)?;
+    return Ok(AST::new(Token::TYPE_SPEC(_type), vec![]));
+  }
+  // constant(&Token) -> Option<AST>
+  fn constant(&self, token: &Token) -> Option<AST> {
+    // the innermost declaration of the name, a parameter or variable hides a constant
+    for (_name, _value) in self.constant_table.iter().rev() {
+      if names::same(_name, token) {
+        match _value.token {
+          Token::EMPTY => return None,
+          _ => return Some(_value.clone())
+        }
+      }
+    }
+    return None;
+  }
+  // declare(&[AST]) -> Result<(), Diagnostic>
+  fn declare(&mut self, nodes: &[AST]) -> Result<(), Diagnostic> {
+    // constants, variables, parameters and routines share the names of the scope they are declared in
+    for _node in nodes {
+      let scope = self.declared.last_mut().unwrap();
+      if scope.iter().any(|name| names::same(name, &_node.token)) {
+        return Err(Diagnostic::with_span(format!("{:?} : {}", _node.token, PANIC_DUPLICATE), _node.span.unwrap_or(self.current_span)));
+      }
+      scope.push(_node.token.clone());
+    }
+    return Ok(());
+  }
+  // hide(&[AST])
+  fn hide(&mut self, nodes: &[AST]) {
+    // parameters and variables hide constants of the same name, until the end of the routine they are declared in
+    for _node in nodes {
+      if self.constant(&_node.token).is_some() {
+        self.constant_table.push((_node.token.clone(), AST::new(Token::EMPTY, vec![])));
+      }
+    }
+  }
+  // constant_expression() -> AST
+  fn constant_expression(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      constant_expression : expression
+    */
+    // evaluated now, so only literals and constants can be used, constants are already replaced by their value
+    let span = self.current_span;
+    let expression = self.expression()?;
+    let mut evaluator = Evaluator::new(vec![], vec![]);
+    match evaluator.constant(&expression) {
+      Ok(_node) => return Ok(_node),
+      // overflow and division by zero are errors in a constant expression, anything else is not one
+      Err(why) if why.message.starts_with(PANIC_EVAL) => return Err(Diagnostic::with_span(format!("{} : {}", PANIC_CONSTANT, why), span)),
+      Err(why) => return Err(Diagnostic::with_span(why.message, span))
+    }
+  }
+  // integer_constant() -> i64
+  fn integer_constant(&mut self) -> Result<i64, Diagnostic> {
+    let span = self.current_span;
+    let node = self.constant_expression()?;
+    match node.token {
+      Token::INTEGER(_int) => return Ok(_int),
+      _ => return Err(Diagnostic::with_span(format!("{:?} : {}", node.token, PANIC_TYPE_MISMATCH), span))
+    }
+  }
   // empty() -> AST
-  fn empty(&mut self) -> AST {
+  fn empty(&mut self) -> Result<AST, Diagnostic> {
     /*
       empty : 
     */
-    return AST::new(Token::EMPTY, vec![])
+    return Ok(AST::new(Token::EMPTY, vec![]));
   }
   // variable : AST
-  fn variable(&mut self) -> AST {
+  fn variable(&mut self) -> Result<AST, Diagnostic> {
     /*
-      variable : ID (LBRA simple_expression RBRA)?
+      variable : ID (LBRA expression RBRA)?
     */
-    let token = self.current_token.clone().unwrap();
+    let token = self.current_token.clone();
+    let span = self.current_span;
     match token {
       Token::ID(_string) => {
         let string = _string.clone();
         // ID
-        self.eat(Token::ID(_string));
-        // indexed variable
-        // if self.current_token.clone().unwrap() == Token::LBRA {
-        //   // LBRA
-        //   self.eat(Token::LBRA);
-        //   // simple_expression
-        //   let simple_expression = self.simple_expression();
-        //   let mut evaluator = Evaluator::new(self.symbol_table.clone(), self.assign_table.clone());
-        //   let index = evaluator.evaluate(&simple_expression);
-        //   let children = AST::new(Token::INTEGER(index as i32), vec![]);
-        //   // RBRA
-        //   self.eat(Token::RBRA);
-        //   return AST::new(Token::ID(string), vec![children]);
-        // }
+        self.eat(Token::ID(_string))?;
+        // indexed variable, the index is evaluated now when it is a constant
+        if self.current_token == Token::LBRA {
+          self.eat(Token::LBRA)?;
+          let index_span = self.current_span;
+          let expression = self.expression()?;
+          self.eat(Token::RBRA)?;
+          let index = match Evaluator::new(vec![], vec![]).constant(&expression) {
+            Ok(_node) if matches!(_node.token, Token::INTEGER(_)) => _node,
+            Ok(_node) => return Err(Diagnostic::with_span(format!("{:?} : {}", _node.token, PANIC_TYPE_MISMATCH), index_span)),
+            Err(_) => expression
+          };
+          return Ok(AST::with_span(Token::ID(string), vec![index], span));
+        }
         // otherwise
-        return AST::new(Token::ID(string), vec![]);
+        return Ok(AST::with_span(Token::ID(string), vec![], span));
       },
-      _ => panic!("{:?} : {}", token, PANIC_SYNTAX)
-    }
-  }
-  // array_type : AST
-  // fn array_type(&mut self, variable_nodes: Vec<AST>) -> AST {
-  //   /*
-  //     array_type : ARRAY LBRA RANGE (COMMA RANGE)* RBRA OF type_spec 
-  //   */
-  //   let token = self.current_token.clone().unwrap();
-  //   match token {
-  //     Token::ARRAY => {
-  //       // ARRAY
-  //       self.eat(Token::ARRAY);
-  //       // LBRA
-  //       self.eat(Token::LBRA);
-  //       // RANGE
-  //       let mut ranges = vec![];
-  //       let token = self.current_token.clone().unwrap();
-  //       match token {
-  //         Token::RANGE(_start, _end) => {
-  //           self.eat(Token::RANGE(_start, _end));
-  //           ranges.push((_start, _end));
-  //         },
-  //         _ => panic!("{:?} : {}", token, PANIC_ARRAY)
-  //       }
-  //       // RBRA
-  //       self.eat(Token::RBRA);
-  //       // OF
-  //       self.eat(Token::OF);
-  //       // type_spec
-  //       let mut node = self.type_spec();
-  //       // build AST
-  //       for mut _variable in variable_nodes {
-  //         // add to symbol_table
-  //         match &node.token.clone() {
-  //           Token::TYPE_SPEC(_type) => {
-  //             self.symbol_table.push((_variable.token.clone(), _type.clone()));
-  //           },
-  //           _ => panic!("{:?} : {}", _variable, PANIC_SYNTAX)
-  //         }
-  //         // TODO: consider changing to dimensions or remove
-  //         for range in &ranges {
-  //           for _ix in range.0 as i32 .. range.1 as i32 + 1 {
-  //             match &_variable.token {
-  //               Token::ID(_string) => {
-  //                 let id = _string.clone();
-  //                 let index = AST::new(Token::INTEGER(_ix), vec![]);
-  //                 let index_id = AST::new(Token::ID(id), vec![index]);
-  //                 node.children.push(index_id);
-  //               },
-  //               _ => panic!("{:?} : {}", _variable, PANIC_SYNTAX)
-  //             }
-  //           }
-  //         }
-  //       }
-  //       return node
-  //     },
-  //     _ => panic!("{:?} : {}", token, PANIC_SYNTAX)
-  //   }
-  // }
-  // structured_type : AST
-  // fn structured_type(&mut self, variable_nodes: Vec<AST>) -> AST {
-  //   /*
-  //     structured_type : (PACKED)? (array_type)
-  //   */
-  //   let token = self.current_token.clone().unwrap();
-  //   match token {
-  //     Token::PACKED => {
-  //       self.eat(Token::PACKED);
-  //       return self.structured_type(variable_nodes);
-  //     },
-  //     Token::ARRAY => {
-  //       return self.array_type(variable_nodes);
-  //     },
-  //     _ => panic!("{:?} : {}", token, PANIC_SYNTAX)
-  //   }
-  // }
-  // constant_declaration : AST
-  // fn constant_declaration(&mut self) -> AST {
-  //   /*
-  //     constant_declaration : ID EQUAL (INTEGER | REAL)
-  //   */
-  //   // ID
-  //   let constant_node = self.variable();
-  //   // EQUAL
-  //   self.eat(Token::EQUAL);
-  //   // (INTEGER | REAL)
-  //   let token = self.current_token.clone().unwrap();
-  //   match token {
-  //     // INTEGER
-  //     Token::INTEGER(_int) => {
-  //       let node = Token::INTEGER(_int);
-  //       // INTEGER
-  //       self.eat(Token::INTEGER(_int));
-  //       self.symbol_table.push((constant_node.token.clone(), Type::INTEGER.clone()));
-  //       // self.result_type = true;
-  //       return AST::new(node, vec![constant_node]);
-  //     },
-  //     // REAL
-  //     Token::REAL(_float) => {
-  //       let node = Token::REAL(_float);
-  //       // INTEGER
-  //       self.eat(Token::REAL(_float));
-  //       self.symbol_table.push((constant_node.token.clone(), Type::REAL.clone()));
-  //       // self.result_type = true;
-  //       return AST::new(node, vec![constant_node]);
-  //     },
-  //     _ => panic!(format!("{:?} : {}", token, PANIC_TYPE_DECLARATION))
-  //   }
-  // }
-  // variable_declaration : AST
-  // fn variable_declaration(&mut self) -> AST {
-  //   /*
-  //     variable_declaration : ID (COMMA ID)* COLON (type_spec | structured_type)
-  //   */
-  //   // ID
-  //   let mut variable_nodes = vec![self.variable()];
-  //   // (COMMA ID)*
-  //   // while self.current_token == Some(Token::COMMA) {
-  //   //   self.eat(Token::COMMA);
-  //   //   variable_nodes.push(self.variable());
-  //   // }
-  //   // COLON
-  //   self.eat(Token::COLON);
-  //   // (type_spec | structured_type)
-  //   let token = self.current_token.clone().unwrap();
-  //   match token {
-  //     // type_spec
-  //     Token::TYPE_SPEC(_type) => {
-  //       let mut node = self.type_spec();
-  //       for _variable in variable_nodes {
-  //         self.symbol_table.push((_variable.token.clone(), _type.clone()));
-  //         node.children.push(_variable);
-  //       }
-  //       return node
-  //     },
-  //     // structured_type
-  //     // Token::PACKED | Token::ARRAY => {
-  //     //   return self.structured_type(variable_nodes);
-  //     // },
-  //     _ => panic!("{:?} : {}", token, PANIC_TYPE_DECLARATION)
-  //   }
-  // }
-  // constant_declarations : AST
-  // fn constant_declarations(&mut self) -> AST {
-  //   /*
-  //     constant_declarations : CONST (constant_declaration SEMICOLON)+ | empty
-  //   */
-  //   let token = self.current_token.clone().unwrap();
-  //   match token {
-  //     Token::CONST => {
-  //       let mut constant_declarations = vec![];
-  //       // CONST
-  //       self.eat(Token::CONST);
-  //       // (constant_declaration SEMICOLON)+
-  //       while let Some(_token) = &self.current_token {
-  //         match _token {
-  //           Token::ID(_string) => {
-  //             constant_declarations.push(self.constant_declaration());
-  //             self.eat(Token::SEMICOLON);
-  //           },
-  //           _ => break
-  //         }
-  //       }
-  //       let node = AST::new(Token::CONST, constant_declarations);
-  //       return node
-  //     },
-  //     _ => return self.empty()
-  //   }
-  // }
-  // variable_declarations : AST
-  // fn variable_declarations(&mut self) -> AST {
-  //   /*
-  //     variable_declarations : VAR (variable_declaration SEMICOLON)+ | empty
-  //   */
-  //   let token = self.current_token.clone().unwrap();
-  //   match token {
-  //     Token::VAR => {
-  //       let mut variable_declarations = vec![];
-  //       // VAR
-  //       self.eat(Token::VAR);
-  //       // (variable_declaration SEMICOLON)+
-  //       while let Some(_token) = &self.current_token {
-  //         match _token {
-  //           Token::ID(_string) => {
-  //             variable_declarations.push(self.variable_declaration());
-  //             self.eat(Token::SEMICOLON);
-  //           },
-  //           _ => break
-  //         }
-  //       }
-  //       let node = AST::new(Token::VAR, variable_declarations);
-  //       return node
-  //     },
-  //     _ => return self.empty()
-  //   }
-  // }
+      _ => return Err(Diagnostic::with_span(format!("{:?} : {}", token, PANIC_SYNTAX), self.current_span))
+    }
+  }
+  // identifier() -> AST
+  fn identifier(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      identifier : ID
+    */
+    // names being declared, which cannot have an index
+    let token = self.current_token.clone();
+    let span = self.current_span;
+    match token {
+      Token::ID(_) => {
+        self.eat(token.clone())?;
+        return Ok(AST::with_span(token, vec![], span));
+      },
+      _ => return Err(Diagnostic::with_span(format!("{:?} : {}", token, PANIC_SYNTAX), span))
+    }
+  }
+  // structured_type(Vec<AST>) -> AST
+  fn structured_type(&mut self, variable_nodes: Vec<AST>) -> Result<AST, Diagnostic> {
+    /*
+      structured_type : (PACKED)? ARRAY LBRA constant_expression RANGE constant_expression RBRA OF type_spec
+    */
+    // PACKED only changes how arrays are stored
+    if self.current_token == Token::PACKED {
+      self.eat(Token::PACKED)?;
+    }
+    self.eat(Token::ARRAY)?;
+    self.eat(Token::LBRA)?;
+    let span = self.current_span;
+    let start = self.integer_constant()?;
+    self.eat(Token::RANGE)?;
+    let end = self.integer_constant()?;
+    if start > end {
+      return Err(Diagnostic::with_span(format!("{}..{} : {}", start, end, PANIC_ARRAY), span));
+    }
+    self.eat(Token::RBRA)?;
+    self.eat(Token::OF)?;
+    // each array has its bounds as child, the elements are kept together
+    let mut node = self.type_spec()?;
+    for _variable in variable_nodes {
+      if let Token::TYPE_SPEC(_type) = &node.token {
+        self.symbol_table.push((_variable.token.clone(), _type.clone()));
+      }
+      let bounds = AST::with_span(Token::RANGE, vec![AST::new(Token::INTEGER(start), vec![]), AST::new(Token::INTEGER(end), vec![])], span);
+      let mut array = _variable;
+      array.children.push(bounds);
+      node.children.push(array);
+    }
+    return Ok(node);
+  }
+  // constant_declaration() -> AST
+  fn constant_declaration(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      constant_declaration : ID EQUAL constant_expression
+    */
+    // ID
+    let constant_node = self.identifier()?;
+    self.declare(std::slice::from_ref(&constant_node))?;
+    // EQUAL
+    self.eat(Token::EQUAL)?;
+    // the value, with the constant as child
+    let mut node = self.constant_expression()?;
+    self.constant_table.push((constant_node.token.clone(), node.clone()));
+    node.children.push(constant_node);
+    return Ok(node);
+  }
+  // constant_declarations() -> AST
+  fn constant_declarations(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      constant_declarations : CONST (constant_declaration SEMICOLON)+
+    */
+    let mut constant_declarations = vec![];
+    // CONST
+    self.eat(Token::CONST)?;
+    // (constant_declaration SEMICOLON)+, each after its doc comments
+    loop {
+      constant_declarations.extend(self.comments());
+      constant_declarations.push(self.constant_declaration()?);
+      self.eat(Token::SEMICOLON)?;
+      match self.current_token {
+        Token::ID(_) => {},
+        _ => break
+      }
+    }
+    return Ok(AST::new(Token::CONST, constant_declarations));
+  }
+  // variable_declaration() -> AST
+  fn variable_declaration(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      variable_declaration : ID (COMMA ID)* COLON (type_spec | structured_type)
+    */
+    // ID
+    let mut variable_nodes = vec![self.identifier()?];
+    // (COMMA ID)*
+    while self.current_token == Token::COMMA {
+      self.eat(Token::COMMA)?;
+      variable_nodes.push(self.identifier()?);
+    }
+    self.declare(&variable_nodes)?;
+    self.hide(&variable_nodes);
+    // COLON
+    self.eat(Token::COLON)?;
+    // structured_type
+    if self.current_token == Token::PACKED || self.current_token == Token::ARRAY {
+      return self.structured_type(variable_nodes);
+    }
+    // type_spec, with the declared variables as children
+    let mut node = self.type_spec()?;
+    if let Token::TYPE_SPEC(_type) = &node.token {
+      for _variable in &variable_nodes {
+        self.symbol_table.push((_variable.token.clone(), _type.clone()));
+      }
+    }
+    node.children = variable_nodes;
+    return Ok(node);
+  }
+  // variable_declarations() -> AST
+  fn variable_declarations(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      variable_declarations : VAR (variable_declaration SEMICOLON)+
+    */
+    let mut variable_declarations = vec![];
+    // VAR
+    self.eat(Token::VAR)?;
+    // (variable_declaration SEMICOLON)+, each after its doc comments
+    loop {
+      variable_declarations.extend(self.comments());
+      variable_declarations.push(self.variable_declaration()?);
+      self.eat(Token::SEMICOLON)?;
+      match self.current_token {
+        Token::ID(_) => {},
+        _ => break
+      }
+    }
+    return Ok(AST::new(Token::VAR, variable_declarations));
+  }
+  // parameter_group() -> AST
+  fn parameter_group(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      parameter_group : (VAR | CONST | OUT)? ID (COMMA ID)* COLON type_spec
+    */
+    // parameters passed by reference are in a VAR or CONST node, OUT is not a reserved word and is passed as VAR
+    let token = self.current_token.clone();
+    let mode = match &token {
+      Token::VAR | Token::CONST => Some(token.clone()),
+      Token::ID(_string) if names::canonical(_string) == "out" && matches!(self.peek(1), Token::ID(_)) => Some(Token::VAR),
+      _ => None
+    };
+    if mode.is_some() {
+      self.eat(token)?;
+    }
+    // ID
+    let mut parameter_nodes = vec![self.identifier()?];
+    // (COMMA ID)*
+    while self.current_token == Token::COMMA {
+      self.eat(Token::COMMA)?;
+      parameter_nodes.push(self.identifier()?);
+    }
+    self.declare(&parameter_nodes)?;
+    self.hide(&parameter_nodes);
+    // COLON
+    self.eat(Token::COLON)?;
+    // type_spec, with the parameters as children
+    let mut node = self.type_spec()?;
+    if let Token::TYPE_SPEC(_type) = &node.token {
+      for _parameter in &parameter_nodes {
+        self.symbol_table.push((_parameter.token.clone(), _type.clone()));
+      }
+    }
+    node.children = parameter_nodes;
+    match mode {
+      Some(_token) => return Ok(AST::new(_token, vec![node])),
+      None => return Ok(node)
+    }
+  }
+  // formal_parameters() -> AST
+  fn formal_parameters(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      formal_parameters : LPAR (parameter_group (SEMICOLON parameter_group)*)? RPAR
+    */
+    let mut parameter_groups = vec![];
+    if self.current_token == Token::LPAR {
+      self.eat(Token::LPAR)?;
+      if self.current_token != Token::RPAR {
+        parameter_groups.push(self.parameter_group()?);
+        while self.current_token == Token::SEMICOLON {
+          self.eat(Token::SEMICOLON)?;
+          parameter_groups.push(self.parameter_group()?);
+        }
+      }
+      self.eat(Token::RPAR)?;
+    }
+    return Ok(AST::new(Token::PARAMETERS, parameter_groups));
+  }
+  // routine_block(usize) -> AST
+  fn routine_block(&mut self, constants: usize) -> Result<AST, Diagnostic> {
+    // constants declared in a procedure or function, and constants hidden by its parameters and variables, are as before it outside
+    let node = self.block()?;
+    self.constant_table.truncate(constants);
+    self.declared.pop();
+    self.eat(Token::SEMICOLON)?;
+    return Ok(node);
+  }
+  // procedure_declaration() -> AST
+  fn procedure_declaration(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      procedure_declaration : PROCEDURE ID formal_parameters? SEMICOLON block SEMICOLON
+    */
+    self.eat(Token::PROCEDURE)?;
+    let name_node = self.identifier()?;
+    self.declare(std::slice::from_ref(&name_node))?;
+    self.declared.push(vec![]);
+    let constants = self.constant_table.len();
+    let parameters_node = self.formal_parameters()?;
+    self.eat(Token::SEMICOLON)?;
+    let block_node = self.routine_block(constants)?;
+    return Ok(AST::new(Token::PROCEDURE, vec![name_node, parameters_node, block_node]));
+  }
+  // function_declaration() -> AST
+  fn function_declaration(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      function_declaration : FUNCTION ID formal_parameters? COLON type_spec SEMICOLON block SEMICOLON
+    */
+    self.eat(Token::FUNCTION)?;
+    let name_node = self.identifier()?;
+    self.declare(std::slice::from_ref(&name_node))?;
+    self.declared.push(vec![]);
+    let constants = self.constant_table.len();
+    let parameters_node = self.formal_parameters()?;
+    // the type of the result
+    self.eat(Token::COLON)?;
+    let type_node = self.type_spec()?;
+    self.eat(Token::SEMICOLON)?;
+    let block_node = self.routine_block(constants)?;
+    return Ok(AST::new(Token::FUNCTION, vec![name_node, parameters_node, type_node, block_node]));
+  }
+  // declarations() -> Vec<AST>
+  fn declarations(&mut self) -> Result<Vec<AST>, Diagnostic> {
+    /*
+      declarations : (CONST (constant_declaration SEMICOLON)+ | VAR (variable_declaration SEMICOLON)+ | procedure_declaration | function_declaration)*
+    */
+    let mut declarations = self.comments();
+    loop {
+      match self.current_token {
+        Token::CONST => declarations.push(self.constant_declarations()?),
+        Token::VAR => declarations.push(self.variable_declarations()?),
+        Token::PROCEDURE => declarations.push(self.procedure_declaration()?),
+        Token::FUNCTION => declarations.push(self.function_declaration()?),
+        _ => break
+      }
+      declarations.extend(self.comments());
+    }
+    return Ok(declarations);
+  }
   // assignment_statement() -> AST
-  fn assignment_statement(&mut self) -> AST {
+  fn assignment_statement(&mut self) -> Result<AST, Diagnostic> {
     /*
-      assignment_statement : variable ASSIGN simple_expression SEMICOLON
+      assignment_statement : variable ASSIGN expression
     */
-    let node = self.variable();
+    let span = self.current_span;
+    let node = self.variable()?;
+    if self.constant(&node.token).is_some() {
+      return Err(Diagnostic::with_span(format!("{:?} : {}", node.token, PANIC_CONSTANT_ASSIGNMENT), span));
+    }
     match self.current_token {
-      Some(Token::ASSIGN) => {
-        self.eat(Token::ASSIGN);
-        let simple_expression = self.simple_expression();
-        let children: Vec<AST> = vec![node.clone(), simple_expression.clone()];
-        self.assign_table.push((node.token, simple_expression));
-        self.eat(Token::SEMICOLON);
+      Token::ASSIGN => {
+        self.eat(Token::ASSIGN)?;
+        // an expression, so booleans can be assigned a comparison
+        let expression = self.expression()?;
+        let children: Vec<AST> = vec![node.clone(), expression.clone()];
+        self.assign_table.push((node.token, expression));
         // self.result_type = false;
         // new branch
-        return AST::new(Token::ASSIGN, children);
+        return Ok(AST::with_span(Token::ASSIGN, children, span));
+      }
+      _ => return Err(Diagnostic::with_span(format!("{:?} : {}", node.token, PANIC_SYNTAX), self.current_span))
+    }
+  }
+  // arguments() -> Vec<AST>
+  fn arguments(&mut self) -> Result<Vec<AST>, Diagnostic> {
+    /*
+      arguments : LPAR (expression (COMMA expression)*)? RPAR
+    */
+    let mut arguments = vec![];
+    self.eat(Token::LPAR)?;
+    if self.current_token != Token::RPAR {
+      arguments.push(self.expression()?);
+      while self.current_token == Token::COMMA {
+        self.eat(Token::COMMA)?;
+        arguments.push(self.expression()?);
       }
-      _ => panic!("{:?} : {}", node.token, PANIC_SYNTAX)
     }
+    self.eat(Token::RPAR)?;
+    return Ok(arguments);
+  }
+  // call_statement() -> AST
+  fn call_statement(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      call_statement : ID arguments?
+    */
+    // the name, then the arguments
+    let span = self.current_span;
+    let mut call_nodes = vec![self.variable()?];
+    if self.current_token == Token::LPAR {
+      call_nodes.extend(self.arguments()?);
+    }
+    return Ok(AST::with_span(Token::CALL, call_nodes, span));
   }
   // factor() -> AST
-  fn factor(&mut self) -> AST {
+  fn factor(&mut self) -> Result<AST, Diagnostic> {
     /*
-      factor : PLUS factor | MINUS factor | INTEGER | REAL | LPAR expression RPAR | variable
+      factor : PLUS factor | MINUS factor | INTEGER | REAL | LPAR expression RPAR | ID arguments | variable
     */
-    let token = self.current_token.clone().unwrap();
+    let token = self.current_token.clone();
+    let span = self.current_span;
     match token {
       Token::PLUS => {
-        self.eat(Token::PLUS);
-        return AST::new(token, vec![self.factor()]);
+        self.eat(Token::PLUS)?;
+        return Ok(AST::with_span(token, vec![self.factor()?], span));
       },
       Token::MINUS => {
-        self.eat(Token::MINUS);
-        return AST::new(token, vec![self.factor()]);
+        self.eat(Token::MINUS)?;
+        // the range of a literal is checked with its sign, so -2147483648 is an integer
+        if let Token::INTEGER(_int) = self.current_token {
+          if i32::try_from(-_int).is_ok() {
+            self.eat(Token::INTEGER(_int))?;
+            return Ok(AST::with_span(Token::INTEGER(-_int), vec![], span));
+          }
+        }
+        return Ok(AST::with_span(token, vec![self.factor()?], span));
       },
       Token::INTEGER(_int) => {
-        self.eat(Token::INTEGER(_int));
-        return AST::new(token, vec![]);
+        self.eat(Token::INTEGER(_int))?;
+        // integers are 32-bit, so a larger literal can only be a real
+        if i32::try_from(_int).is_err() {
+          return Ok(AST::with_span(Token::REAL(_int as f64), vec![], span));
+        }
+        return Ok(AST::with_span(token, vec![], span));
       },
       Token::REAL(_float) => {
-        self.eat(Token::REAL(_float));
-        return AST::new(token, vec![]);
+        self.eat(Token::REAL(_float))?;
+        return Ok(AST::with_span(token, vec![], span));
+      },
+      Token::TRUE | Token::FALSE => {
+        self.eat(token.clone())?;
+        return Ok(AST::with_span(token, vec![], span));
       },
       Token::LPAR => {
-        self.eat(Token::LPAR);
-        let node = self.expression();
-        self.eat(Token::RPAR);
-        return node;
+        self.eat(Token::LPAR)?;
+        let node = self.expression()?;
+        self.eat(Token::RPAR)?;
+        return Ok(node);
       },
       _ => {
-        let node = self.variable();
-        return node;
+        // constants are replaced by their value
+        if let Some(_value) = self.constant(&token) {
+          self.eat(token)?;
+          return Ok(AST::with_span(_value.token, vec![], span));
+        }
+        // a call has arguments in parentheses, a function without parameters can also be called by name alone
+        if let (Token::ID(_), Token::LPAR) = (&token, self.peek(1)) {
+          return self.call_statement();
+        }
+        let node = self.variable()?;
+        return Ok(node);
       }
     }
   }
   // term() -> AST
-  fn term(&mut self) -> AST {
+  fn term(&mut self) -> Result<AST, Diagnostic> {
     /*
       term : factor ((MULTIPLY | DIVIDE | INTEGER_DIV | INTEGER_MOD) factor)*
     */
-    let mut node = self.factor();
-    while self.current_token == Some(Token::MULTIPLY) || self.current_token == Some(Token::DIVIDE) {
+    let mut node = self.factor()?;
+    while [Token::MULTIPLY, Token::DIVIDE, Token::INTEGER_DIV, Token::INTEGER_MOD].contains(&self.current_token) {
+      let span = self.current_span;
       match self.current_token {
-        Some(Token::MULTIPLY) => {
-          self.eat(Token::MULTIPLY);
-          let children: Vec<AST> = vec![node, self.factor()];
-          node = AST::new(Token::MULTIPLY, children);
+        Token::MULTIPLY => {
+          self.eat(Token::MULTIPLY)?;
+          let children: Vec<AST> = vec![node, self.factor()?];
+          node = AST::with_span(Token::MULTIPLY, children, span);
+        },
+        Token::DIVIDE => {
+          self.eat(Token::DIVIDE)?;
+          let children: Vec<AST> = vec![node, self.factor()?];
+          node = AST::with_span(Token::DIVIDE, children, span);
+        },
+        Token::INTEGER_DIV => {
+          self.eat(Token::INTEGER_DIV)?;
+          let children: Vec<AST> = vec![node, self.factor()?];
+          node = AST::with_span(Token::INTEGER_DIV, children, span);
         },
-        Some(Token::DIVIDE) => {
-          self.eat(Token::DIVIDE);
-          let children: Vec<AST> = vec![node, self.factor()];
-          node = AST::new(Token::DIVIDE, children);
+        Token::INTEGER_MOD => {
+          self.eat(Token::INTEGER_MOD)?;
+          let children: Vec<AST> = vec![node, self.factor()?];
+          node = AST::with_span(Token::INTEGER_MOD, children, span);
         },
-        // Some(Token::INTEGER_DIV) => {
-        //   self.eat(Token::INTEGER_DIV);
-        //   let children: Vec<AST> = vec![node, self.factor()];
-        //   node = AST::new(Token::INTEGER_DIV, children);
-        // },
-        // Some(Token::INTEGER_MOD) => {
-        //   self.eat(Token::INTEGER_MOD);
-        //   let children: Vec<AST> = vec![node, self.factor()];
-        //   node = AST::new(Token::INTEGER_MOD, children);
-        // },
-        _ => panic!("{:?} : {}", self.current_token, PANIC_SYNTAX)
+        _ => return Err(Diagnostic::with_span(format!("{:?} : {}", self.current_token, PANIC_SYNTAX), self.current_span))
       }
     }
-    return node;
+    return Ok(node);
   }
   // simple_expression() -> AST
-  fn simple_expression(&mut self) -> AST {
+  fn simple_expression(&mut self) -> Result<AST, Diagnostic> {
     /*
       simple_expression : term ((PLUS | MINUS) term)*
     */
-    let mut node = self.term();
-    while self.current_token == Some(Token::PLUS) || self.current_token == Some(Token::MINUS) {
+    let mut node = self.term()?;
+    while self.current_token == Token::PLUS || self.current_token == Token::MINUS {
+      let span = self.current_span;
       match self.current_token {
-        Some(Token::PLUS) => {
-          self.eat(Token::PLUS);
-          let children: Vec<AST> = vec![node, self.term()];
-          node = AST::new(Token::PLUS, children);
+        Token::PLUS => {
+          self.eat(Token::PLUS)?;
+          let children: Vec<AST> = vec![node, self.term()?];
+          node = AST::with_span(Token::PLUS, children, span);
         },
-        Some(Token::MINUS) => {
-          self.eat(Token::MINUS);
-          let children: Vec<AST> = vec![node, self.term()];
-          node = AST::new(Token::MINUS, children);
+        Token::MINUS => {
+          self.eat(Token::MINUS)?;
+          let children: Vec<AST> = vec![node, self.term()?];
+          node = AST::with_span(Token::MINUS, children, span);
         },
-        _ => panic!("{:?} : {}", self.current_token, PANIC_SYNTAX)
+        _ => return Err(Diagnostic::with_span(format!("{:?} : {}", self.current_token, PANIC_SYNTAX), self.current_span))
       }
     }
-    return node;
+    return Ok(node);
   }
   // expression() -> AST
-  fn expression(&mut self) -> AST {
+  fn expression(&mut self) -> Result<AST, Diagnostic> {
     /*
       (TRUE | FALSE) | ODD LPAR simple_expression RPAR | simple_expression ((EQUAL | GREATER_THAN | GREATER_EQUAL | LESS_THAN | LESS_EQUAL | NOT_EQUAL) simple_expression)?
     */
     let mut node = AST::new(Token::EMPTY, vec![]);
-    // boolean
-    // if self.current_token == Some(Token::TRUE) || self.current_token == Some(Token::FALSE) {
-    //   match self.current_token {
-    //     Some(Token::TRUE) => {
-    //       // TRUE
-    //       self.eat(Token::TRUE);
-    //       node = AST::new(Token::TRUE, vec![]);
-    //     },
-    //     Some(Token::FALSE) => {
-    //       // FALSE
-    //       self.eat(Token::FALSE);
-    //       node = AST::new(Token::FALSE, vec![]);
-    //     },
-    //     _ => {}
-    //   }
-    // }
-    if self.current_token == Some(Token::ODD) {
-      self.eat(Token::ODD);
-      self.eat(Token::LPAR);
-      let children: Vec<AST> = vec![self.simple_expression(), AST::new(Token::INTEGER(2), vec![])];
-      node = AST::new(Token::INTEGER_MOD, children);
-      self.eat(Token::RPAR);
+    if self.current_token == Token::ODD {
+      let span = self.current_span;
+      self.eat(Token::ODD)?;
+      self.eat(Token::LPAR)?;
+      // odd numbers have a remainder when divided by 2, which is -1 for negative numbers
+      let children: Vec<AST> = vec![self.simple_expression()?, AST::new(Token::INTEGER(2), vec![])];
+      let children: Vec<AST> = vec![AST::new(Token::INTEGER_MOD, children), AST::new(Token::INTEGER(0), vec![])];
+      node = AST::with_span(Token::NOT_EQUAL, children, span);
+      self.eat(Token::RPAR)?;
     } else {
-      node = self.simple_expression();
+      node = self.simple_expression()?;
+      let span = self.current_span;
       match self.current_token {
-        Some(Token::EQUAL) => {
-          self.eat(Token::EQUAL);
-          let children: Vec<AST> = vec![node, self.simple_expression()];
-          node = AST::new(Token::EQUAL, children);
+        Token::EQUAL => {
+          self.eat(Token::EQUAL)?;
+          let children: Vec<AST> = vec![node, self.simple_expression()?];
+          node = AST::with_span(Token::EQUAL, children, span);
+        },
+        Token::GREATER_THAN => {
+          self.eat(Token::GREATER_THAN)?;
+          let children: Vec<AST> = vec![node, self.simple_expression()?];
+          node = AST::with_span(Token::GREATER_THAN, children, span);
+        },
+        Token::GREATER_EQUAL => {
+          self.eat(Token::GREATER_EQUAL)?;
+          let children: Vec<AST> = vec![node, self.simple_expression()?];
+          node = AST::with_span(Token::GREATER_EQUAL, children, span);
         },
-        Some(Token::GREATER_THAN) => {
-          self.eat(Token::GREATER_THAN);
-          let children: Vec<AST> = vec![node, self.simple_expression()];
-          node = AST::new(Token::GREATER_THAN, children);
+        Token::LESS_THAN => {
+          self.eat(Token::LESS_THAN)?;
+          let children: Vec<AST> = vec![node, self.simple_expression()?];
+          node = AST::with_span(Token::LESS_THAN, children, span);
         },
-        // Some(Token::GREATER_EQUAL) => {
-        //   self.eat(Token::GREATER_EQUAL);
-        //   let children: Vec<AST> = vec![node, self.simple_expression()];
-        //   node = AST::new(Token::GREATER_EQUAL, children);
-        // },
-        Some(Token::LESS_THAN) => {
-          self.eat(Token::LESS_THAN);
-          let children: Vec<AST> = vec![node, self.simple_expression()];
-          node = AST::new(Token::LESS_THAN, children);
+        Token::LESS_EQUAL => {
+          self.eat(Token::LESS_EQUAL)?;
+          let children: Vec<AST> = vec![node, self.simple_expression()?];
+          node = AST::with_span(Token::LESS_EQUAL, children, span);
+        },
+        Token::NOT_EQUAL => {
+          self.eat(Token::NOT_EQUAL)?;
+          let children: Vec<AST> = vec![node, self.simple_expression()?];
+          node = AST::with_span(Token::NOT_EQUAL, children, span);
         },
-        // Some(Token::LESS_EQUAL) => {
-        //   self.eat(Token::LESS_EQUAL);
-        //   let children: Vec<AST> = vec![node, self.simple_expression()];
-        //   node = AST::new(Token::LESS_EQUAL, children);
-        // },
-        // Some(Token::NOT_EQUAL) => {
-        //   self.eat(Token::NOT_EQUAL);
-        //   let children: Vec<AST> = vec![node, self.simple_expression()];
-        //   node = AST::new(Token::NOT_EQUAL, children);
-        // },
         _ => {}
       }
     }
-    return node;
-  }
-  // function_statement() -> AST
-  // fn function_statement(&mut self) -> AST {
-  //   /*
-  //     function_statement : (WRITELN) LPAR expression RPAR
-  //   */
-  //   match self.current_token {
-  //     Some(Token::WRITELN) => {
-  //       // WRITELN
-  //       self.eat(Token::WRITELN);
-  //       // LPAR
-  //       self.eat(Token::LPAR);
-  //       let node = AST::new(Token::WRITELN, vec![self.expression()]);
-  //       // RPAR
-  //       self.eat(Token::RPAR);
-  //       // self.result_type = true;
-  //       return node
-  //     }
-  //     _ => panic!("{:?} : {}", self.current_token, PANIC_SYNTAX)
-  //   }
-  // }
+    return Ok(node);
+  }
   // while_statement() -> AST
-  fn while_statement(&mut self) -> AST {
+  fn while_statement(&mut self) -> Result<AST, Diagnostic> {
     /*
       while_statement : WHILE expression DO statement
     */
-    self.eat(Token::WHILE);
-    let expression = self.expression();
-    self.eat(Token::DO);
-    let statement = self.statement();
+    self.eat(Token::WHILE)?;
+    let expression = self.expression()?;
+    self.eat(Token::DO)?;
+    let statement = self.statement()?;
     let node = AST::new(Token::WHILE, vec![expression, statement]);
-    return node;
+    return Ok(node);
   }
   // if_statement() -> AST
-  fn if_statement(&mut self) -> AST {
+  fn if_statement(&mut self) -> Result<AST, Diagnostic> {
     /*
       if_statement : IF expression THEN statement (ELSE statement)?
     */
-    self.eat(Token::IF);
-    let expression = self.expression();
-    self.eat(Token::THEN);
-    let statement = self.statement();
+    self.eat(Token::IF)?;
+    let expression = self.expression()?;
+    self.eat(Token::THEN)?;
+    let statement = self.statement()?;
     // else
-    if self.current_token == Some(Token::ELSE) {
-      self.eat(Token::ELSE);
-      let else_statement = self.statement();
+    if self.current_token == Token::ELSE {
+      self.eat(Token::ELSE)?;
+      let else_statement = self.statement()?;
       let node = AST::new(Token::IF, vec![expression, statement, else_statement]);
-      return node;
+      return Ok(node);
     } else {
       let node = AST::new(Token::IF, vec![expression, statement]);
-      return node;
+      return Ok(node);
     }
   }
   // structured_statement() -> AST TODO: merge this
-  fn structured_statement(&mut self) -> AST {
+  fn structured_statement(&mut self) -> Result<AST, Diagnostic> {
     /*
       structured_statement : if_statement | while_statement 
     */
-    let token = self.current_token.clone().unwrap();
+    let token = self.current_token.clone();
     match token {
       Token::IF => {
         return self.if_statement();
@@ -583,15 +780,15 @@ impl Parser {
       Token::WHILE => {
         return self.while_statement();
       },
-      _ => panic!("{:?} : {}", self.current_token, PANIC_SYNTAX)
+      _ => return Err(Diagnostic::with_span(format!("{:?} : {}", self.current_token, PANIC_SYNTAX), self.current_span))
     }
   }
   // statement() -> AST
-  fn statement(&mut self) -> AST {
+  fn statement(&mut self) -> Result<AST, Diagnostic> {
     /*
-      statement : compound_statement | structured_statement | assignment_statement | function_statement | empty
+      statement : compound_statement | structured_statement | assignment_statement | call_statement | empty
     */
-    let token = self.current_token.clone().unwrap();
+    let token = self.current_token.clone();
     match token {
       Token::BEGIN => {
         return self.compound_statement();
@@ -600,70 +797,150 @@ impl Parser {
         return self.structured_statement();
       },
       Token::ID(_string) => {
-        return self.assignment_statement();
+        match self.peek(1) {
+          Token::ASSIGN | Token::LBRA => return self.assignment_statement(),
+          _ => return self.call_statement()
+        }
       },
-      // Token::WRITELN => {
-      //   return self.function_statement();
-      // },
       _ => return self.empty()
     }
   }
   // statement_list() -> AST
-  fn statement_list(&mut self) -> Vec<AST> {
+  fn statement_list(&mut self) -> Result<Vec<AST>, Diagnostic> {
     /*
       statement_list : statement (SEMICOLON statement)*
     */
-    let mut statement_list_nodes = vec![];
-    let node = self.statement();
-    statement_list_nodes.push(node);
-    while self.current_token == Some(Token::SEMICOLON) {
-      self.eat(Token::SEMICOLON);
-      statement_list_nodes.push(self.statement());
-    } 
-    return statement_list_nodes;
+    let mut statement_list_nodes = self.comments();
+    let mut node = self.statement()?;
+    loop {
+      // empty statements, such as the one after a final semicolon, are left out
+      if node.token != Token::EMPTY {
+        statement_list_nodes.push(node);
+      }
+      if self.current_token != Token::SEMICOLON {
+        break;
+      }
+      self.eat(Token::SEMICOLON)?;
+      statement_list_nodes.extend(self.comments());
+      node = self.statement()?;
+    }
+    return Ok(statement_list_nodes);
   }
   // compound_statement() -> AST
-  fn compound_statement(&mut self) -> AST {
+  fn compound_statement(&mut self) -> Result<AST, Diagnostic> {
     /*
       compound_statement : BEGIN statement_list END
     */
-    self.eat(Token::BEGIN);
-    let compound_statement_nodes = self.statement_list();
-    self.eat(Token::END);
-    return AST::new(Token::BLOCK, compound_statement_nodes)
+    self.eat(Token::BEGIN)?;
+    let mut compound_statement_nodes = self.statement_list()?;
+    compound_statement_nodes.extend(self.comments());
+    self.eat(Token::END)?;
+    return Ok(AST::new(Token::BLOCK, compound_statement_nodes));
   }
   // block() -> AST
-  fn block(&mut self) -> AST {
+  fn block(&mut self) -> Result<AST, Diagnostic> {
     /*
       block : declarations compound_statement
     */
-    // let variable_declarations_nodes = self.variable_declarations();
-    let compound_statement_nodes = self.compound_statement();
-    // let node = AST::new(Token::BLOCK, vec![variable_declarations_nodes, compound_statement_nodes]);
-    let node = AST::new(Token::BLOCK, vec![compound_statement_nodes]);
-    return node
+    let mut nodes = self.declarations()?;
+    nodes.push(self.compound_statement()?);
+    let node = AST::new(Token::BLOCK, nodes);
+    return Ok(node);
   }
   // program() -> AST
-  // fn program(&mut self) -> AST {
-  //   /*
-  //     program : PROGRAM variable SEMICOLON block DOT
-  //   */
-  //   self.eat(Token::PROGRAM);
-  //   let variable_node = self.variable();
-  //   self.eat(Token::SEMICOLON);
-  //   let block_node = self.block();
-  //   let program_node = AST::new(Token::PROGRAM, vec![variable_node, block_node]);
-  //   self.eat(Token::DOT);
-  //   return program_node
-  // }
+  fn program(&mut self) -> Result<AST, Diagnostic> {
+    /*
+      program : PROGRAM ID (LPAR ID (COMMA ID)* RPAR)? SEMICOLON block DOT
+    */
+    self.eat(Token::PROGRAM)?;
+    let variable_node = self.identifier()?;
+    // program parameters such as (input, output) name external files and are not used
+    if self.current_token == Token::LPAR {
+      self.eat(Token::LPAR)?;
+      self.identifier()?;
+      while self.current_token == Token::COMMA {
+        self.eat(Token::COMMA)?;
+        self.identifier()?;
+      }
+      self.eat(Token::RPAR)?;
+    }
+    self.eat(Token::SEMICOLON)?;
+    let block_node = self.block()?;
+    let program_node = AST::new(Token::PROGRAM, vec![variable_node, block_node]);
+    self.eat(Token::DOT)?;
+    return Ok(program_node);
+  }
   // parse() -> AST
-  pub fn parse(&mut self) -> AST {
-    // let node = self.block();
-    let node = self.assignment_statement();
-    if self.current_token != Some(Token::EOF) {
-      panic!("{:?} : {}", self.current_token, PANIC_SYNTAX)
+  pub fn parse(&mut self) -> Result<AST, Diagnostic> {
+    let mut nodes = self.comments();
+    nodes.push(self.program()?);
+    nodes.extend(self.comments());
+    let node = if nodes.len() == 1 { nodes.remove(0) } else { AST::new(Token::BLOCK, nodes) };
+    self.eof()?;
+    self.dump(&node);
+    return Ok(node);
+  }
+  // parse_statement() -> AST
+  pub fn parse_statement(&mut self) -> Result<AST, Diagnostic> {
+    // statements separated by semicolons, in a block when there is more than one
+    let mut nodes = self.statement_list()?;
+    nodes.extend(self.comments());
+    let node = match nodes.len() {
+      0 => AST::new(Token::EMPTY, vec![]),
+      1 => nodes.remove(0),
+      _ => AST::new(Token::BLOCK, nodes)
+    };
+    self.eof()?;
+    self.dump(&node);
+    return Ok(node);
+  }
+  // parse_declarations() -> AST
+  pub fn parse_declarations(&mut self) -> Result<AST, Diagnostic> {
+    // declarations without a program, as entered in the repl
+    let mut nodes = self.declarations()?;
+    nodes.extend(self.comments());
+    let node = AST::new(Token::BLOCK, nodes);
+    self.eof()?;
+    self.dump(&node);
+    return Ok(node);
+  }
+  // parse_expression() -> AST
+  pub fn parse_expression(&mut self) -> Result<AST, Diagnostic> {
+    let node = self.expression()?;
+    self.eof()?;
+    self.dump(&node);
+    return Ok(node);
+  }
+  // diagnostics(Diagnostic) -> Diagnostics
+  pub fn diagnostics(&mut self, error: Diagnostic) -> Diagnostics {
+    // an error at a token from an included file is reported with its name
+    let error = match error.span {
+      Some(_) if error.file.is_none() => Diagnostic { file: self.preprocessor.file(), ..error },
+      _ => error
+    };
+    // read the rest of the source so every lexical error is reported, not only the first
+    self.preprocessor.by_ref().count();
+    let mut diagnostics = self.preprocessor.diagnostics();
+    // a syntax error at an ERROR token repeats a lexical error
+    match self.current_token {
+      Token::ERROR(_) => {},
+      _ => diagnostics.push(error)
     }
-    return node;
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.file.clone(), diagnostic.span.map(|span| span.start)));
+    return Diagnostics::new(diagnostics);
+  }
+  // dump
+  fn dump(&self, node: &AST) {
+    if self.options.dump_ast { eprintln!("{:?}", node); };
+    if self.options.dump_symbols { eprintln!("{:?}", self.symbol_table); };
+    if self.options.dump_symbols { eprintln!("{:?}", self.assign_table); };
+  }
+  // eof
+  fn eof(&mut self) -> Result<(), Diagnostic> {
+    if self.current_token != Token::EOF {
+      return Err(Diagnostic::with_span(format!("{:?} : {}", self.current_token, PANIC_SYNTAX), self.current_span))
+    }
+    return Ok(());
   }
 }
 
@@ -684,7 +961,7 @@ mod tests {
   #[test]
   fn assignment_statement() {
     let lexer = Lexer::new("test := 42;".to_string());
-    let tree = Parser::new(lexer).assignment_statement();
+    let tree = Parser::new(lexer).unwrap().assignment_statement().unwrap();
     assert_eq!(
       tree.to_string().replace(" ", ""),
       "AST {
@@ -701,5 +978,197 @@ mod tests {
         ]
       }".replace("\n", "").replace(" ", "")
     );
+    let tree = Parser::new(Lexer::new("x := 5000000000".to_string())).unwrap().assignment_statement().unwrap();
+    assert_eq!(tree.children[1].token, Token::REAL(5000000000.0));
+    let tree = Parser::new(Lexer::new("x := -2147483648".to_string())).unwrap().assignment_statement().unwrap();
+    assert_eq!(tree.children[1].token, Token::INTEGER(-2147483648));
+    let tree = Parser::new(Lexer::new("x := $FFFFFFFF - -1".to_string())).unwrap().assignment_statement().unwrap();
+    assert_eq!(tree.children[1], AST::new(Token::MINUS, vec![AST::new(Token::INTEGER(-1), vec![]), AST::new(Token::INTEGER(-1), vec![])]));
+    let tree = Parser::new(Lexer::new("x := -2147483649".to_string())).unwrap().assignment_statement().unwrap();
+    assert_eq!(tree.children[1], AST::new(Token::MINUS, vec![AST::new(Token::REAL(2147483649.0), vec![])]));
+    // constant indexes are evaluated, any other is kept as an expression
+    let tree = Parser::new(Lexer::new("v[2 * 3] := v[i + 1]".to_string())).unwrap().assignment_statement().unwrap();
+    assert_eq!(tree.children[0].children[0].token, Token::INTEGER(6));
+    assert_eq!(tree.children[1].children[0].token, Token::PLUS);
+    assert!(Parser::new(Lexer::new("v[1.5] := 0".to_string())).unwrap().assignment_statement().is_err());
+  }
+
+  #[test]
+  fn peek() {
+    let lexer = Lexer::new("x := 1; y".to_string());
+    let mut parser = Parser::new(lexer).unwrap();
+    assert_eq!(parser.current_token, Token::ID("x".to_string()));
+    assert_eq!(parser.peek(1), Token::ASSIGN);
+    assert_eq!(parser.peek(4), Token::ID("y".to_string()));
+    assert_eq!(parser.current_token, Token::ID("x".to_string()));
+    let diagnostic = Parser::new(Lexer::new("x 1".to_string())).unwrap().parse_statement().unwrap_err();
+    assert_eq!(diagnostic.span, Some(Span::new(2, 3, 1, 3)));
+  }
+
+  #[test]
+  fn diagnostics() {
+    let mut parser = Parser::new(Lexer::new("x := 1 ? 2;\ny := \"a\";".to_string())).unwrap();
+    let error = parser.parse_statement().unwrap_err();
+    let diagnostics = parser.diagnostics(error);
+    let messages: Vec<String> = diagnostics.diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+    assert_eq!(messages, vec![
+      format!("1:8 : ? : {}", PANIC_SYNTAX),
+      format!("2:6 : \" : {}", PANIC_SYNTAX),
+      format!("2:8 : \" : {}", PANIC_SYNTAX),
+    ]);
+    let mut parser = Parser::new(Lexer::new("x := ; ?".to_string())).unwrap();
+    let error = parser.parse_statement().unwrap_err();
+    assert_eq!(parser.diagnostics(error).diagnostics.len(), 2);
+  }
+
+  #[test]
+  fn comments() {
+    let options = Options { trivia: true, ..Options::default() };
+    let source = "begin { first } x := 1; (* second\n  line *) // last\nend";
+    let tree = Parser::with_options(Lexer::with_options(source.to_string(), &options), &options).unwrap().parse_statement().unwrap();
+    let tokens: Vec<Token> = tree.children.iter().map(|node| node.token.clone()).collect();
+    assert_eq!(tokens, vec![
+      Token::COMMENT("{ first }".to_string()),
+      Token::ASSIGN,
+      Token::COMMENT("(* second\n  line *)".to_string()),
+      Token::COMMENT("// last".to_string()),
+    ]);
+    let tree = Parser::new(Lexer::new(source.to_string())).unwrap().parse_statement().unwrap();
+    assert_eq!(tree.children.len(), 1);
+  }
+
+  #[test]
+  fn program() {
+    let source = "program Test(output);\nvar a, b: real;\n  c: integer;\nbegin\n  a := 1;\n  if a <> 1 then b := 2 else c := 3;\nend.";
+    let tree = Parser::new(Lexer::new(source.to_string())).unwrap().parse().unwrap();
+    assert_eq!(tree.token, Token::PROGRAM);
+    assert_eq!(tree.children[0].token, Token::ID("Test".to_string()));
+    let block = &tree.children[1];
+    assert_eq!(block.children[0].token, Token::VAR);
+    assert_eq!(block.children[0].children[0].token, Token::TYPE_SPEC(Type::REAL));
+    assert_eq!(block.children[0].children[0].children.len(), 2);
+    assert_eq!(block.children[1].children.len(), 2);
+    assert_eq!(block.children[1].children[1].children[0].token, Token::NOT_EQUAL);
+    // the final dot ends the program
+    assert!(Parser::new(Lexer::new("program p; begin end".to_string())).unwrap().parse().is_err());
+    assert!(Parser::new(Lexer::new("program p; begin end. x".to_string())).unwrap().parse().is_err());
+  }
+
+  #[test]
+  fn variable_declarations() {
+    let options = Options { trivia: true, ..Options::default() };
+    let source = "program p; var { counts }\n  i, j: Integer; n: longint; r: REAL; f: boolean; begin end.";
+    let mut parser = Parser::with_options(Lexer::with_options(source.to_string(), &options), &options).unwrap();
+    let tree = parser.parse().unwrap();
+    assert_eq!(parser.symbol_table, vec![
+      (Token::ID("i".to_string()), Type::INTEGER),
+      (Token::ID("j".to_string()), Type::INTEGER),
+      (Token::ID("n".to_string()), Type::INTEGER),
+      (Token::ID("r".to_string()), Type::REAL),
+      (Token::ID("f".to_string()), Type::BOOLEAN),
+    ]);
+    // doc comments stay in front of the declaration they describe
+    let declarations = &tree.children[1].children[0];
+    assert_eq!(declarations.children[0].token, Token::COMMENT("{ counts }".to_string()));
+    assert_eq!(declarations.children[1].token, Token::TYPE_SPEC(Type::INTEGER));
+    let diagnostic = Parser::new(Lexer::new("program p; var s: text; begin end.".to_string())).unwrap().parse().unwrap_err();
+    assert_eq!(diagnostic.to_string(), format!("1:19 : ID(\"text\") : {}", PANIC_TYPE_DECLARATION));
+    // names being declared have no index
+    let diagnostic = Parser::new(Lexer::new("program p; var x[3]: integer; begin end.".to_string())).unwrap().parse().unwrap_err();
+    assert_eq!(diagnostic.to_string(), format!("1:17 : COLON : {}", PANIC_SYNTAX));
+    assert!(Parser::new(Lexer::new("program p; procedure q(a[1]: integer); begin end; begin end.".to_string())).unwrap().parse().is_err());
+  }
+
+  #[test]
+  fn constant_declarations() {
+    let source = "program p; const N = 10; Half = N div 2; Rate = N / 4; var v: packed array[1..Half] of integer; begin v[Half - 1] := N end.";
+    let mut parser = Parser::new(Lexer::new(source.to_string())).unwrap();
+    let tree = parser.parse().unwrap();
+    assert_eq!(parser.constant_table.iter().map(|constant| constant.1.token.clone()).collect::<Vec<Token>>(), vec![Token::INTEGER(10), Token::INTEGER(5), Token::REAL(2.5)]);
+    let mut parser = Parser::new(Lexer::new("program p; const Min = -2147483648; Mask = $FFFFFFFF; begin end.".to_string())).unwrap();
+    parser.parse().unwrap();
+    assert_eq!(parser.constant_table.iter().map(|constant| constant.1.token.clone()).collect::<Vec<Token>>(), vec![Token::INTEGER(-2147483648), Token::INTEGER(-1)]);
+    // an array has its bounds, not one variable for each element
+    let declarations = &tree.children[1].children[1].children[0];
+    assert_eq!(declarations.children.len(), 1);
+    assert_eq!(declarations.children[0], AST::new(Token::ID("v".to_string()), vec![AST::new(Token::RANGE, vec![AST::new(Token::INTEGER(1), vec![]), AST::new(Token::INTEGER(5), vec![])])]));
+    // constants are replaced by their value
+    let assignment = &tree.children[1].children[2].children[0];
+    assert_eq!(assignment.children[0].children[0].token, Token::INTEGER(4));
+    assert_eq!(assignment.children[1].token, Token::INTEGER(10));
+    let error = |source: &str| Parser::new(Lexer::new(source.to_string())).unwrap().parse().unwrap_err().to_string();
+    assert_eq!(error("program p; const n = 1; begin N := 2 end."), format!("1:31 : ID(\"N\") : {}", PANIC_CONSTANT_ASSIGNMENT));
+    assert!(error("program p; var i: integer; const n = i + 1; begin end.").starts_with(&format!("1:38 : {}", PANIC_CONSTANT)));
+    assert!(error("program p; var v: array[3..1] of real; begin end.").ends_with(PANIC_ARRAY));
+    assert!(error("program p; var v: array[1..3] of real; begin v[1.5] := 0 end.").ends_with(PANIC_TYPE_MISMATCH));
+    assert!(error("program p; const n = 7 mod 0; begin end.").starts_with(&format!("1:22 : {}", PANIC_DIVISION_BY_ZERO)));
+    assert!(error("program p; const n = 65536 * 32768; begin end.").starts_with(&format!("1:22 : {}", PANIC_OVERFLOW)));
+  }
+
+  #[test]
+  fn routine_declarations() {
+    let source = "program p; const N = 1; procedure Show(a, b: integer; r: real); const M = 2; begin writeln(a + M) end; function Twice(x: real): real; begin Twice := 2 * x end; begin Show(N, 2, Twice(1.5)); Show end.";
+    let mut parser = Parser::new(Lexer::new(source.to_string())).unwrap();
+    let tree = parser.parse().unwrap();
+    let block = &tree.children[1];
+    assert_eq!(block.children.iter().map(|node| node.token.clone()).collect::<Vec<Token>>(), vec![Token::CONST, Token::PROCEDURE, Token::FUNCTION, Token::BLOCK]);
+    // parameters are grouped by type, as variables are
+    let parameters = &block.children[1].children[1];
+    assert_eq!(parameters.token, Token::PARAMETERS);
+    assert_eq!(parameters.children[0].token, Token::TYPE_SPEC(Type::INTEGER));
+    assert_eq!(parameters.children[0].children.len(), 2);
+    assert_eq!(parameters.children[1].token, Token::TYPE_SPEC(Type::REAL));
+    assert_eq!(block.children[2].children[2].token, Token::TYPE_SPEC(Type::REAL));
+    // constants of a procedure are not seen outside it
+    assert_eq!(parser.constant_table.len(), 1);
+    // calls have the name and the arguments as children, in statements and expressions
+    let statements = &block.children[3].children;
+    assert_eq!(statements[0].token, Token::CALL);
+    assert_eq!(statements[0].children.len(), 4);
+    assert_eq!(statements[0].children[3].token, Token::CALL);
+    assert_eq!(statements[1], AST::new(Token::CALL, vec![AST::new(Token::ID("Show".to_string()), vec![])]));
+    // parameters passed by reference are grouped in a VAR or CONST node, OUT is passed as VAR but can still be a name
+    let tree = Parser::new(Lexer::new("program p; procedure q(var a: integer; const b: real; out c, d: integer; out: boolean); begin end; begin end.".to_string())).unwrap().parse().unwrap();
+    let parameters = &tree.children[1].children[0].children[1];
+    assert_eq!(parameters.children.iter().map(|node| node.token.clone()).collect::<Vec<Token>>(), vec![Token::VAR, Token::CONST, Token::VAR, Token::TYPE_SPEC(Type::BOOLEAN)]);
+    assert_eq!(parameters.children[2].children[0].children.len(), 2);
+    assert_eq!(parameters.children[3].children[0].token, Token::ID("out".to_string()));
+    // parameters and local variables hide a constant of the same name, which is seen again after the routine
+    let mut parser = Parser::new(Lexer::new("program p; const N = 10; function F(n: integer): integer; begin F := n end; procedure q; var n: integer; begin n := 1 end; begin writeln(N) end.".to_string())).unwrap();
+    let tree = parser.parse().unwrap();
+    let block = &tree.children[1];
+    assert_eq!(block.children[1].children[3].children[0].children[0].children[1].token, Token::ID("n".to_string()));
+    assert_eq!(block.children[2].children[2].children[1].children[0].children[0].token, Token::ID("n".to_string()));
+    assert_eq!(block.children[3].children[0].children[1].token, Token::INTEGER(10));
+    assert_eq!(parser.constant_table.len(), 1);
+    // a name is declared once in each scope, a routine has a scope of its own
+    let error = |source: &str| Parser::new(Lexer::new(source.to_string())).unwrap().parse().unwrap_err().to_string();
+    assert_eq!(error("program p; var x, X: integer; begin end."), format!("1:19 : ID(\"X\") : {}", PANIC_DUPLICATE));
+    assert_eq!(error("program p; procedure q; begin end; function Q: integer; begin end; begin end."), format!("1:45 : ID(\"Q\") : {}", PANIC_DUPLICATE));
+    assert_eq!(error("program p; var q: real; procedure q; begin end; begin end."), format!("1:35 : ID(\"q\") : {}", PANIC_DUPLICATE));
+    assert_eq!(error("program p; const N = 1; var n: real; begin end."), format!("1:29 : ID(\"n\") : {}", PANIC_DUPLICATE));
+    assert_eq!(error("program p; procedure q(a: integer); var a: real; begin end; begin end."), format!("1:41 : ID(\"a\") : {}", PANIC_DUPLICATE));
+    assert!(Parser::new(Lexer::new("program p; var x: integer; procedure q(x: integer); var y: real; begin end; procedure r; var y: real; begin end; begin end.".to_string())).unwrap().parse().is_ok());
+    // functions need the type of their result
+    assert!(Parser::new(Lexer::new("program p; function f; begin end; begin end.".to_string())).unwrap().parse().is_err());
+  }
+
+  #[test]
+  fn statement_list() {
+    let tree = Parser::new(Lexer::new("begin x := 1; y := 2 end".to_string())).unwrap().parse_statement().unwrap();
+    assert_eq!(tree.children.len(), 2);
+    let tree = Parser::new(Lexer::new("x := 1; y := 2;".to_string())).unwrap().parse_statement().unwrap();
+    assert_eq!(tree.token, Token::BLOCK);
+    assert!(Parser::new(Lexer::new("if x = 1 then y := 1; else y := 2".to_string())).unwrap().parse_statement().is_err());
+  }
+
+  #[test]
+  fn current_span() {
+    let lexer = Lexer::new("begin\n  x := ;\nend".to_string());
+    let mut parser = Parser::new(lexer).unwrap();
+    assert_eq!(parser.current_span, Span::new(0, 5, 1, 1));
+    let diagnostic = parser.parse_statement().unwrap_err();
+    assert_eq!(diagnostic.span, Some(Span::new(13, 14, 2, 8)));
+    assert!(diagnostic.to_string().starts_with("2:8 : "));
   }
 }
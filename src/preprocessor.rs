@@ -0,0 +1,370 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+use crate::consts::*;
+use crate::token::{ Token, Span, Trivia };
+use crate::lexer::Lexer;
+use crate::diagnostic::Diagnostic;
+use crate::options::Options;
+
+// includes nested deeper than this are taken to include themselves
+const MAX_INCLUDE_DEPTH             : usize = 16;
+// extension tried when an include file is not found as given
+const INCLUDE_EXTENSION             : &str = "inc";
+
+/*
+
+  {$I file} or {$INCLUDE file}          read file in place of the directive
+  {$DEFINE X} or {$UNDEF X}             define or undefine symbol X
+  {$IFDEF X} or {$IFNDEF X}             read what follows only if X is defined, or not defined
+  {$ELSE}                               read what follows only if the branch before was skipped
+  {$ENDIF}                              end of conditional block
+  {$R+}, {$Q-,R+} or {$MODE objfpc}     switches, kept for later phases
+
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Condition {
+  // whether the block the condition is in is read
+  outer: bool,
+  // whether the current branch is read
+  active: bool,
+  // whether ELSE has been seen
+  otherwise: bool,
+}
+
+struct Include {
+  // path of the included file
+  path: String,
+  lexer: Lexer,
+}
+
+pub struct Preprocessor {
+  lexer: Lexer,
+  // files being included, innermost last
+  stack: Vec<Include>,
+  // tokens read ahead by peek, with the included file they are in
+  buffer: VecDeque<(Span, Token, Vec<Trivia>, Option<String>)>,
+  // trivia before directives, kept for the next token read
+  pending: Vec<Trivia>,
+  // span, trivia and included file of the last token returned
+  span: Span,
+  trivia: Vec<Trivia>,
+  file: Option<String>,
+  // conditional blocks being read, innermost last
+  conditions: Vec<Condition>,
+  // symbols defined with $DEFINE or before the source is read, in upper case
+  pub defines: Vec<String>,
+  // switch directives with their argument, such as ("R", "+") or ("MODE", "objfpc")
+  pub switches: Vec<(String, String)>,
+  // files read with $I
+  pub files: Vec<String>,
+  diagnostics: Vec<Diagnostic>,
+  options: Options,
+}
+
+impl Preprocessor {
+  // new(Lexer, &Options) -> Preprocessor
+  pub fn new(lexer: Lexer, options: &Options) -> Preprocessor {
+    return Preprocessor {
+      lexer: lexer,
+      stack: vec![],
+      buffer: VecDeque::new(),
+      pending: vec![],
+      span: Span::default(),
+      trivia: vec![],
+      file: None,
+      conditions: vec![],
+      defines: options.defines.iter().map(|define| define.to_uppercase()).collect(),
+      switches: vec![],
+      files: vec![],
+      diagnostics: vec![],
+      options: options.clone(),
+    };
+  }
+  // active() -> bool
+  fn active(&self) -> bool {
+    match self.conditions.last() {
+      Some(_condition) => return _condition.outer && _condition.active,
+      None => return true
+    }
+  }
+  // switch(&str) -> Option<&str>
+  pub fn switch(&self, name: &str) -> Option<&str> {
+    let name = name.to_uppercase();
+    return self.switches.iter().find(|(_name, _)| *_name == name).map(|(_, _value)| _value.as_str());
+  }
+  // set_switch(String, String)
+  fn set_switch(&mut self, name: String, value: String) {
+    match self.switches.iter_mut().find(|(_name, _)| *_name == name) {
+      Some(_switch) => _switch.1 = value,
+      None => self.switches.push((name, value))
+    }
+  }
+  // error(String, Span)
+  fn error(&mut self, message: String, span: Span) {
+    // the span is in the file being read
+    let file = self.stack.last().map(|include| include.path.clone());
+    self.diagnostics.push(Diagnostic { file: file, ..Diagnostic::with_span(message, span) });
+  }
+  // directive(&str, Span)
+  fn directive(&mut self, text: &str, span: Span) {
+    let length = text.find(|_char: char| !(_char.is_alphanumeric() || _char == '_')).unwrap_or(text.len());
+    let name = text[..length].to_uppercase();
+    let argument = text[length..].trim();
+    match name.as_str() {
+      // conditions are followed even where nothing is read, to find the matching $ENDIF
+      "IFDEF" | "IFNDEF" => {
+        let defined = self.defines.contains(&argument.to_uppercase());
+        let outer = self.active();
+        self.conditions.push(Condition { outer: outer, active: defined == (name == "IFDEF"), otherwise: false });
+      },
+      "ELSE" => {
+        match self.conditions.last_mut() {
+          Some(_condition) if !_condition.otherwise => {
+            _condition.active = !_condition.active;
+            _condition.otherwise = true;
+          },
+          _ => self.error(format!("$ELSE : {}", PANIC_DIRECTIVE), span)
+        }
+      },
+      "ENDIF" => {
+        if self.conditions.pop().is_none() {
+          self.error(format!("$ENDIF : {}", PANIC_DIRECTIVE), span);
+        }
+      },
+      _ if !self.active() => {},
+      "DEFINE" | "UNDEF" if argument.is_empty() => self.error(format!("${} : {}", name, PANIC_DIRECTIVE), span),
+      "DEFINE" => {
+        let symbol = argument.to_uppercase();
+        if !self.defines.contains(&symbol) {
+          self.defines.push(symbol);
+        }
+      },
+      "UNDEF" => {
+        let symbol = argument.to_uppercase();
+        self.defines.retain(|define| *define != symbol);
+      },
+      // $I+ and $I- are the io checking switch
+      "I" | "INCLUDE" if !argument.starts_with('+') && !argument.starts_with('-') => self.include(argument, span),
+      "" => self.error(format!("${} : {}", text, PANIC_DIRECTIVE), span),
+      // switches such as R+ or Q-,R+
+      _ if argument.starts_with('+') || argument.starts_with('-') => {
+        for _switch in text.split(',').map(str::trim) {
+          match _switch.char_indices().last() {
+            Some((_index, _sign)) if _index > 0 && (_sign == '+' || _sign == '-') => self.set_switch(_switch[.._index].to_uppercase(), _sign.to_string()),
+            _ => self.error(format!("${} : {}", _switch, PANIC_DIRECTIVE), span)
+          }
+        }
+      },
+      // switches with an argument, such as MODE objfpc
+      _ => self.set_switch(name, argument.to_string())
+    }
+  }
+  // include(&str, Span)
+  fn include(&mut self, argument: &str, span: Span) {
+    let name = argument.trim_matches('\'');
+    if name.is_empty() || self.stack.len() >= MAX_INCLUDE_DEPTH {
+      self.error(format!("$I {} : {}", argument, PANIC_DIRECTIVE), span);
+      return;
+    }
+    // the directory of the file with the directive, then the unit paths (the compiler puts the directory of the input first), then the current directory
+    let mut directories = vec![];
+    if let Some(_include) = self.stack.last() {
+      directories.push(Path::new(&_include.path).parent().unwrap_or(Path::new("")).to_path_buf());
+    }
+    directories.extend(self.options.unit_paths.iter().map(|path| Path::new(path).to_path_buf()));
+    directories.push(Path::new("").to_path_buf());
+    let mut names = vec![name.to_string()];
+    if Path::new(name).extension().is_none() {
+      names.push(format!("{}.{}", name, INCLUDE_EXTENSION));
+    }
+    for _directory in &directories {
+      for _name in &names {
+        let path = _directory.join(_name);
+        if let Ok(_source) = fs::read_to_string(&path) {
+          let path = path.to_string_lossy().to_string();
+          if !self.files.contains(&path) {
+            self.files.push(path.clone());
+          }
+          let lexer = Lexer::with_options(_source, &self.options);
+          self.stack.push(Include { path: path, lexer: lexer });
+          return;
+        }
+      }
+    }
+    self.error(format!("{} : {}", name, PANIC_INCLUDE), span);
+  }
+  // read() -> (Span, Token, Vec<Trivia>, Option<String>)
+  fn read(&mut self) -> (Span, Token, Vec<Trivia>, Option<String>) {
+    loop {
+      let file = self.stack.last().map(|include| include.path.clone());
+      let (span, token, trivia) = match self.stack.last_mut() {
+        Some(_include) => {
+          let token = _include.lexer.get_next_token();
+          (_include.lexer.span(), token, _include.lexer.trivia().to_vec())
+        },
+        None => {
+          let token = self.lexer.get_next_token();
+          (self.lexer.span(), token, self.lexer.trivia().to_vec())
+        }
+      };
+      // trivia of skipped source is dropped with it
+      if self.active() {
+        self.pending.extend(trivia);
+      }
+      match token {
+        Token::EOF if !self.stack.is_empty() => {
+          // end of an included file, errors in it are reported with its name
+          let include = self.stack.pop().unwrap();
+          for _diagnostic in include.lexer.diagnostics() {
+            self.diagnostics.push(Diagnostic { file: Some(include.path.clone()), ..(*_diagnostic).clone() });
+          }
+        },
+        Token::EOF => {
+          if !self.conditions.is_empty() {
+            self.conditions.clear();
+            self.error(format!("{}", PANIC_ENDIF), span);
+          }
+          return (span, token, std::mem::take(&mut self.pending), file);
+        },
+        Token::DIRECTIVE(_text) => self.directive(&_text, span),
+        _ if self.active() => return (span, token, std::mem::take(&mut self.pending), file),
+        _ => {}
+      }
+    }
+  }
+  // fill(usize)
+  fn fill(&mut self, count: usize) {
+    while self.buffer.len() < count {
+      let token = self.read();
+      self.buffer.push_back(token);
+    }
+  }
+  // peek(usize) -> Token
+  pub fn peek(&mut self, offset: usize) -> Token {
+    self.fill(offset + 1);
+    return self.buffer[offset].1.clone();
+  }
+  // peek_span(usize) -> Span
+  pub fn peek_span(&mut self, offset: usize) -> Span {
+    self.fill(offset + 1);
+    return self.buffer[offset].0;
+  }
+  // get_next_token() -> Token
+  pub fn get_next_token(&mut self) -> Token {
+    self.fill(1);
+    let (span, token, trivia, file) = self.buffer.pop_front().unwrap();
+    self.span = span;
+    self.trivia = trivia;
+    self.file = file;
+    return token;
+  }
+  // trivia() -> &[Trivia]
+  pub fn trivia(&self) -> &[Trivia] {
+    return &self.trivia;
+  }
+  // span() -> Span
+  pub fn span(&self) -> Span {
+    return self.span;
+  }
+  // file() -> Option<String>
+  pub fn file(&self) -> Option<String> {
+    return self.file.clone();
+  }
+  // diagnostics() -> Vec<Diagnostic>
+  pub fn diagnostics(&self) -> Vec<Diagnostic> {
+    let mut diagnostics = self.lexer.diagnostics().to_vec();
+    diagnostics.extend(self.diagnostics.iter().cloned());
+    return diagnostics;
+  }
+}
+
+impl Iterator for Preprocessor {
+  type Item = Token;
+  // next() -> Option<Token>
+  fn next(&mut self) -> Option<Token> {
+    match self.get_next_token() {
+      Token::EOF => return None,
+      _token => return Some(_token)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // preprocess(&str, &Options) -> (Vec<Token>, Preprocessor)
+  fn preprocess(source: &str, options: &Options) -> (Vec<Token>, Preprocessor) {
+    let mut preprocessor = Preprocessor::new(Lexer::with_options(source.to_string(), options), options);
+    let tokens = preprocessor.by_ref().collect();
+    return (tokens, preprocessor);
+  }
+
+  #[test]
+  fn conditions() {
+    let source = "{$DEFINE A} {$IFDEF a} 1 {$IFNDEF B} 2 {$ELSE} 3 {$ENDIF} {$ELSE} 4 {$IFDEF A} 5 {$ENDIF} {$ENDIF} 6";
+    let (tokens, preprocessor) = preprocess(source, &Options::default());
+    assert_eq!(tokens, vec![Token::INTEGER(1), Token::INTEGER(2), Token::INTEGER(6)]);
+    assert!(preprocessor.diagnostics().is_empty());
+    let options = Options { defines: vec!["b".to_string()], ..Options::default() };
+    let (tokens, _) = preprocess("{$IFDEF B} 1 {$UNDEF B} {$ENDIF} {$IFDEF B} 2 {$ENDIF}", &options);
+    assert_eq!(tokens, vec![Token::INTEGER(1)]);
+    let (tokens, preprocessor) = preprocess("{$ENDIF} {$IFDEF A} 1", &Options::default());
+    assert!(tokens.is_empty());
+    let messages: Vec<String> = preprocessor.diagnostics().iter().map(|diagnostic| diagnostic.message.clone()).collect();
+    assert_eq!(messages, vec![format!("$ENDIF : {}", PANIC_DIRECTIVE), PANIC_ENDIF.to_string()]);
+  }
+
+  #[test]
+  fn trivia() {
+    let options = Options { trivia: true, ..Options::default() };
+    let mut preprocessor = Preprocessor::new(Lexer::with_options("{ a }{$IFDEF X}{ b } 1{$ENDIF}{ c }{$R+}2".to_string(), &options), &options);
+    assert_eq!(preprocessor.get_next_token(), Token::INTEGER(2));
+    assert_eq!(preprocessor.trivia(), &[Trivia::COMMENT("{ a }".to_string()), Trivia::COMMENT("{ c }".to_string())]);
+  }
+
+  #[test]
+  fn switches() {
+    let (tokens, preprocessor) = preprocess("{$R+} {$mode objfpc} {$Q-,R-} {$I+} 1", &Options::default());
+    assert_eq!(tokens, vec![Token::INTEGER(1)]);
+    assert_eq!(preprocessor.switch("r"), Some("-"));
+    assert_eq!(preprocessor.switch("MODE"), Some("objfpc"));
+    assert_eq!(preprocessor.switch("Q"), Some("-"));
+    assert_eq!(preprocessor.switch("I"), Some("+"));
+    assert_eq!(preprocessor.switch("H"), None);
+  }
+
+  #[test]
+  fn include() {
+    let directory = std::env::temp_dir().join(format!("pas2wat-include-{}", std::process::id()));
+    fs::create_dir_all(directory.join("inner")).unwrap();
+    fs::write(directory.join("a.inc"), "2 {$I inner/b.inc} 4").unwrap();
+    fs::write(directory.join("inner").join("b.inc"), "3 ?").unwrap();
+    let options = Options { unit_paths: vec![directory.to_string_lossy().to_string()], ..Options::default() };
+    let (tokens, preprocessor) = preprocess("1 {$I a} 5 {$I missing.inc}", &options);
+    assert_eq!(tokens, vec![Token::INTEGER(1), Token::INTEGER(2), Token::INTEGER(3), Token::ERROR("?".to_string()), Token::INTEGER(4), Token::INTEGER(5)]);
+    assert_eq!(preprocessor.files.len(), 2);
+    let diagnostics = preprocessor.diagnostics();
+    assert_eq!(diagnostics.len(), 2);
+    // errors in an included file are at their place in it
+    assert_eq!(diagnostics[0].span, Some(Span::new(2, 3, 1, 3)));
+    assert!(diagnostics[0].file.as_ref().unwrap().ends_with("b.inc"));
+    assert_eq!(diagnostics[0].message, format!("? : {}", PANIC_SYNTAX));
+    assert_eq!(diagnostics[1].message, format!("missing.inc : {}", PANIC_INCLUDE));
+    assert_eq!(diagnostics[1].file, None);
+    // the directory of the including file is searched before the unit paths
+    fs::write(directory.join("c.inc"), "6").unwrap();
+    fs::write(directory.join("inner").join("c.inc"), "7").unwrap();
+    fs::write(directory.join("inner").join("d.inc"), "{$I c.inc}").unwrap();
+    let (tokens, _) = preprocess("{$I inner/d.inc} {$I c.inc}", &options);
+    assert_eq!(tokens, vec![Token::INTEGER(7), Token::INTEGER(6)]);
+    // a file including itself stops at the depth limit
+    fs::write(directory.join("self.inc"), "{$I self.inc}").unwrap();
+    let (_, preprocessor) = preprocess("{$I self.inc}", &options);
+    assert_eq!(preprocessor.diagnostics().len(), 1);
+    fs::remove_dir_all(&directory).unwrap();
+  }
+}
@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::Path;
+
+use crate::consts::*;
+use crate::diagnostic::Diagnostic;
+use crate::options::{ Dialect, Options, Target };
+
+const DEFAULT_OUT_DIR               : &str = "build";
+const DEFAULT_EMIT                  : &str = "wat";
+
+/*
+
+  programs   = ["programs/test.pas"]   # entry programs, required
+  unit-paths = ["units"]               # searched for units and include files
+  out-dir    = "build"                 # output directory
+  target     = "browser"               # browser | wasi | bare
+  dialect    = "turbo"                 # iso | turbo | delphi
+  emit       = ["wat", "wasm"]         # tokens | ast | symbols | wat | wasm | js
+  comments   = true                    # include compiler comments in generated code
+  nested-comments = false              # allow nested { } and (* *) comments
+  source-comments = false              # include comments from the source in generated code
+  defines    = ["DEBUG"]               # symbols defined for {$IFDEF}
+
+*/
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Project {
+  // entry programs compiled by a build
+  pub programs: Vec<String>,
+  // directory every program is written to
+  pub out_dir: String,
+  // stages written for every program
+  pub emit: Vec<String>,
+  // target, dialect, unit paths and comments
+  pub options: Options,
+}
+
+impl Project {
+  // parse(&str) -> Result<Project, Diagnostic>
+  pub fn parse(source: &str) -> Result<Project, Diagnostic> {
+    let table = match source.parse::<toml::Table>() {
+      Ok(_table) => _table,
+      Err(why) => return Err(Diagnostic::new(format!("{} : {}", PANIC_PROJECT, why.message())))
+    };
+    let mut project = Project {
+      programs: vec![],
+      out_dir: DEFAULT_OUT_DIR.to_string(),
+      emit: vec![DEFAULT_EMIT.to_string()],
+      options: Options::default(),
+    };
+    for (_key, _value) in &table {
+      match _key.as_str() {
+        "programs" => project.programs = strings(_key, _value)?,
+        "unit-paths" => project.options.unit_paths = strings(_key, _value)?,
+        "defines" => project.options.defines = strings(_key, _value)?,
+        "out-dir" => project.out_dir = string(_key, _value)?,
+        "emit" => project.emit = strings(_key, _value)?,
+        "target" => {
+          let name = string(_key, _value)?;
+          match Target::parse(&name) {
+            Some(_target) => project.options.target = _target,
+            None => return Err(Diagnostic::new(format!("{} : {} : unknown target {}", PANIC_PROJECT, _key, name)))
+          }
+        },
+        "dialect" => {
+          let name = string(_key, _value)?;
+          match Dialect::parse(&name) {
+            Some(_dialect) => project.options.dialect = _dialect,
+            None => return Err(Diagnostic::new(format!("{} : {} : unknown dialect {}", PANIC_PROJECT, _key, name)))
+          }
+        },
+        "comments" => {
+          match _value.as_bool() {
+            Some(_comments) => project.options.comments = _comments,
+            None => return Err(Diagnostic::new(format!("{} : {} : expected true or false", PANIC_PROJECT, _key)))
+          }
+        },
+        "nested-comments" => {
+          match _value.as_bool() {
+            Some(_nested_comments) => project.options.nested_comments = _nested_comments,
+            None => return Err(Diagnostic::new(format!("{} : {} : expected true or false", PANIC_PROJECT, _key)))
+          }
+        },
+        "source-comments" => {
+          match _value.as_bool() {
+            Some(_source_comments) => project.options.trivia = _source_comments,
+            None => return Err(Diagnostic::new(format!("{} : {} : expected true or false", PANIC_PROJECT, _key)))
+          }
+        },
+        _ => return Err(Diagnostic::new(format!("{} : {} : unknown key", PANIC_PROJECT, _key)))
+      }
+    }
+    if project.programs.is_empty() {
+      return Err(Diagnostic::new(format!("{} : programs : no entry programs", PANIC_PROJECT)));
+    }
+    return Ok(project);
+  }
+  // load(&str) -> Result<Project, Diagnostic>
+  pub fn load(path: &str) -> Result<Project, Diagnostic> {
+    let source = match fs::read_to_string(path) {
+      Ok(_source) => _source,
+      Err(why) => return Err(Diagnostic::new(format!("{} : {} : {}", PANIC_READ, path, why)))
+    };
+    let mut project = match Project::parse(&source) {
+      Ok(_project) => _project,
+      Err(why) => return Err(Diagnostic::new(format!("{} : {}", path, why)))
+    };
+    // paths in the project file are relative to the directory it is in
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    let resolve = |file: &String| directory.join(file).to_string_lossy().to_string();
+    project.programs = project.programs.iter().map(resolve).collect();
+    project.options.unit_paths = project.options.unit_paths.iter().map(resolve).collect();
+    project.out_dir = resolve(&project.out_dir);
+    return Ok(project);
+  }
+}
+
+// string(&str, Value) -> Result<String, Diagnostic>
+fn string(key: &str, value: &toml::Value) -> Result<String, Diagnostic> {
+  match value.as_str() {
+    Some(_string) => return Ok(_string.to_string()),
+    None => return Err(Diagnostic::new(format!("{} : {} : expected a string", PANIC_PROJECT, key)))
+  }
+}
+
+// strings(&str, Value) -> Result<Vec<String>, Diagnostic>
+fn strings(key: &str, value: &toml::Value) -> Result<Vec<String>, Diagnostic> {
+  match value.as_array() {
+    Some(_array) => return _array.iter().map(|value| string(key, value)).collect(),
+    None => return Err(Diagnostic::new(format!("{} : {} : expected a list of strings", PANIC_PROJECT, key)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse() {
+    let project = Project::parse("
+      programs = [\"a.pas\", \"b.pas\"]
+      unit-paths = [\"units\"]
+      target = \"wasi\"
+      emit = [\"wat\", \"wasm\"]
+      comments = false
+      defines = [\"DEBUG\"]
+    ").unwrap();
+    assert_eq!(project.programs, vec!["a.pas".to_string(), "b.pas".to_string()]);
+    assert_eq!(project.out_dir, DEFAULT_OUT_DIR.to_string());
+    assert_eq!(project.emit, vec!["wat".to_string(), "wasm".to_string()]);
+    assert_eq!(project.options, Options {
+      comments: false,
+      target: Target::WASI,
+      unit_paths: vec!["units".to_string()],
+      defines: vec!["DEBUG".to_string()],
+      ..Options::default()
+    });
+    assert!(Project::parse("programs = []").is_err());
+    assert!(Project::parse("programs = [\"a.pas\"]\ntarget = \"node\"").is_err());
+    assert!(Project::parse("programs = [\"a.pas\"]\noutput = \"a.wat\"").is_err());
+  }
+}
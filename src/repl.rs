@@ -0,0 +1,222 @@
+use std::io::{ self, BufRead, Write };
+
+use crate::ast::AST;
+use crate::diagnostic::Diagnostics;
+use crate::interpreter::Interpreter;
+use crate::lexer::Lexer;
+use crate::options::Options;
+use crate::parser::Parser;
+use crate::token::Token;
+
+const PROMPT                        : &str = "pas2wat> ";
+const PROMPT_CONTINUE               : &str = "     ...> ";
+const HELP                          : &str = "enter a declaration, a statement or an expression, or one of
+
+  :wat    show or hide the WAT generated for each line
+  :vars   show all variables and their values
+  :reset  forget all declarations and variables
+  :help   show this message
+  :quit   leave the repl";
+
+pub struct Repl {
+  interpreter: Interpreter,
+  options: Options,
+  // show the WAT generated for each line
+  pub show_wat: bool,
+}
+
+impl Repl {
+  // new(&Options) -> Repl
+  pub fn new(options: &Options) -> Repl {
+    let repl = Repl {
+      interpreter: Interpreter::new(),
+      options: options.clone(),
+      show_wat: false,
+    };
+    return repl;
+  }
+  // parser(&str) -> Result<Parser, Diagnostics>
+  fn parser(&self, source: &str) -> Result<Parser, Diagnostics> {
+    // each line is parsed with the session options and the constants declared so far
+    let mut parser = Parser::with_options(Lexer::with_options(source.to_string(), &self.options), &self.options)?;
+    parser.constant_table = self.interpreter.constants.clone();
+    return Ok(parser);
+  }
+  // parse(&str) -> Result<AST, Diagnostics>
+  fn parse(&self, source: &str) -> Result<AST, Diagnostics> {
+    // declarations, statements, then expressions
+    let first = crate::tokenize(source, &self.options).ok().and_then(|tokens| tokens.first().cloned());
+    if matches!(first, Some(Token::CONST | Token::VAR | Token::PROCEDURE | Token::FUNCTION)) {
+      // the last semicolon can be left out, as after a statement
+      let source = if source.ends_with(';') { source.to_string() } else { format!("{};", source) };
+      let mut parser = self.parser(&source)?;
+      match parser.parse_declarations() {
+        Ok(_tree) => return Ok(_tree),
+        Err(why) => return Err(parser.diagnostics(why))
+      }
+    }
+    let mut parser = self.parser(source)?;
+    let error = match parser.parse_statement() {
+      // a name alone is a variable, unless a procedure or function has that name
+      Ok(_tree) if _tree.token == Token::CALL && _tree.children.len() == 1 && !self.interpreter.callable(&_tree.children[0].token) => None,
+      Ok(_tree) => return Ok(_tree),
+      Err(why) => Some(parser.diagnostics(why)),
+    };
+    let mut parser = self.parser(source)?;
+    match parser.parse_expression() {
+      Ok(_tree) => return Ok(_tree),
+      Err(why) => return Err(error.unwrap_or_else(|| parser.diagnostics(why)))
+    }
+  }
+  // complete(&str) -> bool
+  fn complete(&self, source: &str) -> bool {
+    // a line is continued until every BEGIN has a matching END
+    match crate::tokenize(source, &self.options) {
+      Ok(_tokens) => {
+        let begins = _tokens.iter().filter(|token| **token == Token::BEGIN).count();
+        let ends = _tokens.iter().filter(|token| **token == Token::END).count();
+        return begins <= ends;
+      },
+      Err(_) => return true
+    }
+  }
+  // eval(&str) -> Result<String, Diagnostics>
+  pub fn eval(&mut self, source: &str) -> Result<String, Diagnostics> {
+    let tree = self.parse(source)?;
+    let mut output = String::new();
+    // the line is run even when the emitter does not support it yet
+    if self.show_wat {
+      match crate::emit(&tree, &self.options) {
+        Ok(_wat) => output.push_str(&_wat),
+        Err(_diagnostics) => output.push_str(&format!("error: {}\n", _diagnostics)),
+      }
+    }
+    let value = self.interpreter.run(&tree);
+    // text from writeln comes before the value
+    output.push_str(&std::mem::take(&mut self.interpreter.output));
+    match (&tree.token, value?) {
+      (Token::ASSIGN, Some(_value)) => output.push_str(&format!("{} = {}\n", name(&tree.children[0]), _value)),
+      (_, Some(_value)) => output.push_str(&format!("{}\n", _value)),
+      (_, None) => {}
+    }
+    return Ok(output);
+  }
+  // command(&str) -> Option<String>
+  fn command(&mut self, command: &str) -> Option<String> {
+    match command {
+      ":wat" => {
+        self.show_wat = !self.show_wat;
+        return Some(format!("wat {}\n", if self.show_wat { "on" } else { "off" }));
+      },
+      ":vars" => {
+        let mut output = String::new();
+        for (_name, _value) in self.interpreter.variables() {
+          output.push_str(&format!("{} = {}\n", _name, _value));
+        }
+        return Some(output);
+      },
+      ":reset" => {
+        self.interpreter = Interpreter::new();
+        return Some(String::new());
+      },
+      ":help" => return Some(format!("{}\n", HELP)),
+      ":quit" | ":q" => return None,
+      _ => return Some(format!("error: {} : unknown command, try :help\n", command))
+    }
+  }
+  // run(BufRead, Write)
+  pub fn run<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> io::Result<()> {
+    let mut source = String::new();
+    write!(output, "{}", PROMPT)?;
+    output.flush()?;
+    for _line in input.lines() {
+      let line = _line?;
+      if source.is_empty() && line.trim().starts_with(':') {
+        match self.command(line.trim()) {
+          Some(_output) => write!(output, "{}", _output)?,
+          None => return Ok(())
+        }
+      } else {
+        source.push_str(&line);
+        source.push('\n');
+        if !self.complete(&source) {
+          write!(output, "{}", PROMPT_CONTINUE)?;
+          output.flush()?;
+          continue;
+        }
+        match self.eval(source.trim()) {
+          Ok(_output) => write!(output, "{}", _output)?,
+          Err(_diagnostics) => {
+            for _diagnostic in &_diagnostics.diagnostics {
+              writeln!(output, "error: {}", _diagnostic)?;
+            }
+          }
+        }
+        source.clear();
+      }
+      write!(output, "{}", PROMPT)?;
+      output.flush()?;
+    }
+    writeln!(output)?;
+    return Ok(());
+  }
+}
+
+// name(AST) -> String
+fn name(node: &AST) -> String {
+  match &node.token {
+    Token::ID(_string) => return _string.clone(),
+    _token => return format!("{:?}", _token)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn eval() {
+    let mut repl = Repl::new(&Options { comments: false, ..Options::default() });
+    assert_eq!(repl.eval("x := 6 * 7"), Ok("x = 42\n".to_string()));
+    assert_eq!(repl.eval("x / 2"), Ok("21\n".to_string()));
+    assert_eq!(repl.eval("x"), Ok("42\n".to_string()));
+    assert!(repl.eval("y + 1").is_err());
+    repl.show_wat = true;
+    assert_eq!(repl.eval("x := 1;"), Ok(";; this file is generated\n(local.set $x\n  (f64.const 1)\n)\nx = 1\n".to_string()));
+  }
+
+  #[test]
+  fn declarations() {
+    let mut repl = Repl::new(&Options::default());
+    assert_eq!(repl.eval("var x, y: integer;"), Ok(String::new()));
+    assert_eq!(repl.eval("x + y"), Ok("0\n".to_string()));
+    assert_eq!(repl.eval("const N = 3;"), Ok(String::new()));
+    assert_eq!(repl.eval("x := N * 2"), Ok("x = 6\n".to_string()));
+    assert!(repl.eval("N := 4").is_err());
+    assert_eq!(repl.eval("procedure P; begin x := x + N end"), Ok(String::new()));
+    assert_eq!(repl.eval("P"), Ok(String::new()));
+    assert_eq!(repl.eval("x"), Ok("9\n".to_string()));
+    assert_eq!(repl.eval("procedure Swap(var a, b: integer); var t: integer; begin t := a; a := b; b := t end;"), Ok(String::new()));
+    assert_eq!(repl.eval("Swap(x, y)"), Ok(String::new()));
+    assert_eq!(repl.eval("x"), Ok("0\n".to_string()));
+    assert!(repl.eval("t").is_err());
+    assert_eq!(repl.eval("function Fact(n: integer): integer; begin if n < 2 then Fact := 1 else Fact := n * Fact(n - 1) end;"), Ok(String::new()));
+    assert_eq!(repl.eval("Fact(5) + y"), Ok("129\n".to_string()));
+    assert_eq!(repl.eval("writeln(3, x + 1)"), Ok("3\n1\n".to_string()));
+    assert!(repl.eval("var ;").is_err());
+    // the session options are used for every line
+    let mut repl = Repl::new(&Options { nested_comments: true, ..Options::default() });
+    assert_eq!(repl.eval("{ { } } var x: integer;"), Ok(String::new()));
+  }
+
+  #[test]
+  fn run() {
+    let mut repl = Repl::new(&Options::default());
+    let mut output = vec![];
+    repl.run("begin\nx := 2;\nend\nx + 1\n:vars\n:quit\n".as_bytes(), &mut output).unwrap();
+    assert_eq!(
+      String::from_utf8(output).unwrap(),
+      format!("{}{}{}{}3\n{}x = 2\n{}", PROMPT, PROMPT_CONTINUE, PROMPT_CONTINUE, PROMPT, PROMPT, PROMPT)
+    );
+  }
+}
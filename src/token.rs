@@ -1,19 +1,21 @@
 #[derive(Clone, Debug, PartialEq)]
 pub enum Type {
-  // INTEGER,
-  // REAL,
-  // BOOLEAN,
+  INTEGER,
+  REAL,
+  BOOLEAN,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Token {
   // BOF,
-  // TYPE_SPEC(Type),
-  INTEGER(i32),
+  TYPE_SPEC(Type),
+  INTEGER(i64),
   REAL(f64),
-  RANGE(i32, i32),
-  // TRUE,
-  // FALSE,
+  STRING(String),
+  CHAR(char),
+  TRUE,
+  FALSE,
+  // operators
   PLUS,
   MINUS,
   MULTIPLY,
@@ -27,23 +29,38 @@ pub enum Token {
   ODD,
   LPAR,
   RPAR,
-  // LBRA,
-  // RBRA,
+  LBRA,
+  RBRA,
   ID(String),
   ASSIGN,
   BLOCK,
+  // call of a procedure or function, with the name and arguments as children
+  CALL,
+  // formal parameters of a procedure or function
+  PARAMETERS,
   SEMICOLON,
-  // COLON,
-  // COMMA,
-  // DOT,
-  // PROGRAM,
-  // VAR,
-  // CONST,
-  // PACKED,
-  // ARRAY,
-  // OF,
-  // INTEGER_DIV,
+  COLON,
+  COMMA,
+  DOT,
+  // ..
+  RANGE,
+  // ^
+  CARET,
+  // @
+  AT,
+  // reserved words
+  PROGRAM,
+  VAR,
+  CONST,
+  PACKED,
+  ARRAY,
+  OF,
+  INTEGER_DIV,
   INTEGER_MOD,
+  AND,
+  OR,
+  NOT,
+  IN,
   BEGIN,
   END,
   EMPTY,
@@ -52,6 +69,96 @@ pub enum Token {
   IF,
   THEN,
   ELSE,
+  CASE,
+  FOR,
+  TO,
+  DOWNTO,
+  REPEAT,
+  UNTIL,
+  WITH,
+  GOTO,
+  LABEL,
+  TYPE,
+  RECORD,
+  SET,
+  FILE,
+  NIL,
+  PROCEDURE,
+  FUNCTION,
+  // reserved words in turbo pascal
+  ASM,
+  CONSTRUCTOR,
+  DESTRUCTOR,
+  IMPLEMENTATION,
+  INHERITED,
+  INLINE,
+  INTERFACE,
+  OBJECT,
+  SHL,
+  SHR,
+  STRING_TYPE,
+  UNIT,
+  USES,
+  XOR,
+  // reserved words in delphi
+  AS,
+  CLASS,
+  EXCEPT,
+  EXPORTS,
+  FINALIZATION,
+  FINALLY,
+  INITIALIZATION,
+  IS,
+  LIBRARY,
+  PROPERTY,
+  RAISE,
+  RESOURCESTRING,
+  THREADVAR,
+  TRY,
   // WRITELN,
+  // text of a {$ } directive, read by the preprocessor
+  DIRECTIVE(String),
+  // comment passed through to generated code
+  COMMENT(String),
+  // text the lexer could not read, reported in its diagnostics
+  ERROR(String),
   EOF
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Trivia {
+  // spaces, tabs and newlines
+  WHITESPACE(String),
+  // comment with its delimiters, such as { note } or // note
+  COMMENT(String),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+  // byte offset of the first character
+  pub start: usize,
+  // byte offset after the last character
+  pub end: usize,
+  // line of the first character, starting at 1
+  pub line: usize,
+  // column of the first character, starting at 1
+  pub column: usize,
+}
+
+impl Span {
+  // new(usize, usize, usize, usize) -> Span
+  pub fn new(start: usize, end: usize, line: usize, column: usize) -> Span {
+    Span {
+      start: start,
+      end: end,
+      line: line,
+      column: column,
+    }
+  }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}